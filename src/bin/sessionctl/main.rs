@@ -38,8 +38,16 @@ struct Args {
 enum Command {
     Inspect(InspectCommand),
     Start(StartCommand),
+    Isolate(IsolateCommand),
     Stop(StopCommand),
     Restart(RestartCommand),
+    Reload(ReloadCommand),
+    Kill(KillCommand),
+    Cat(CatCommand),
+    Jobs(JobsCommand),
+    Cancel(CancelCommand),
+    DaemonReload(DaemonReloadCommand),
+    Run(RunCommand),
 }
 
 #[derive(FromArgs, PartialEq, Debug)]
@@ -52,16 +60,104 @@ struct InspectCommand {}
 #[argh(subcommand, name = "start")]
 struct StartCommand {}
 
+#[derive(FromArgs, PartialEq, Debug)]
+/// Switch the session to a target, stopping everything outside of it
+#[argh(subcommand, name = "isolate")]
+struct IsolateCommand {}
+
 #[derive(FromArgs, PartialEq, Debug)]
 /// Stop a target from within sessionrunner
 #[argh(subcommand, name = "stop")]
-struct StopCommand {}
+struct StopCommand {
+    #[argh(switch)]
+    /// stop the target even if nodes requiring it are running
+    force: bool,
+
+    #[argh(switch)]
+    /// stop every node depending on the target first
+    cascade: bool,
+}
 
 #[derive(FromArgs, PartialEq, Debug)]
 /// Restart a target from within sessionrunner
 #[argh(subcommand, name = "restart")]
 struct RestartCommand {}
 
+#[derive(FromArgs, PartialEq, Debug)]
+/// Ask a target to reload its configuration without restarting it
+#[argh(subcommand, name = "reload")]
+struct ReloadCommand {}
+
+#[derive(FromArgs, PartialEq, Debug)]
+/// Send a signal to the processes of a target
+#[argh(subcommand, name = "kill")]
+struct KillCommand {
+    #[argh(option, short = 't')]
+    /// the target to send the signal to (overrides the global target)
+    target: Option<String>,
+
+    #[argh(option, short = 's')]
+    /// the signal to be sent, for example SIGUSR1
+    signal: String,
+
+    #[argh(option, short = 'w', default = "String::from(\"main\")")]
+    /// either "main" for the main process only or "group" for the whole process group
+    whom: String,
+}
+
+#[derive(FromArgs, PartialEq, Debug)]
+/// Show the file a target has been loaded from
+#[argh(subcommand, name = "cat")]
+struct CatCommand {
+    #[argh(option, short = 't')]
+    /// the target to be shown (overrides the global target)
+    target: Option<String>,
+}
+
+#[derive(FromArgs, PartialEq, Debug)]
+/// List pending and recently finished jobs
+#[argh(subcommand, name = "jobs")]
+struct JobsCommand {}
+
+#[derive(FromArgs, PartialEq, Debug)]
+/// Cancel a job that is still waiting to be run
+#[argh(subcommand, name = "cancel")]
+struct CancelCommand {
+    #[argh(positional)]
+    /// the identifier of the job, as listed by jobs
+    id: u64,
+}
+
+#[derive(FromArgs, PartialEq, Debug)]
+/// Reload every descriptor and apply the changes to the running session
+#[argh(subcommand, name = "daemon-reload")]
+struct DaemonReloadCommand {}
+
+#[derive(FromArgs, PartialEq, Debug)]
+/// Run a command as a transient node supervised by sessionrunner
+#[argh(subcommand, name = "run")]
+struct RunCommand {
+    #[argh(option, short = 'n')]
+    /// the name of the transient node, generated if not given
+    name: Option<String>,
+
+    #[argh(switch)]
+    /// keep the node once it stops so that it can be started again
+    keep: bool,
+
+    #[argh(option, default = "0")]
+    /// how many times the command is restarted if it fails
+    max_restarts: u64,
+
+    #[argh(option, default = "0")]
+    /// seconds to wait before restarting the command
+    restart_delay_secs: u64,
+
+    #[argh(positional, greedy)]
+    /// the command to run followed by its arguments
+    command: Vec<String>,
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let connection = Connection::session().await?;
@@ -75,14 +171,162 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     };
 
     match &args.command {
-        Command::Stop(_stop_command) => {
-            proxy.stop(target).await.unwrap();
+        Command::Stop(stop_command) => {
+            let mode = match (stop_command.cascade, stop_command.force) {
+                (true, _) => "cascade",
+                (false, true) => "force",
+                (false, false) => "plain",
+            };
+
+            let (status, result, stopped) = proxy.stop(target.clone(), mode.to_owned()).await?;
+            if status != 0 {
+                eprintln!("Error stopping {target}: {result}");
+                std::process::exit(status as i32)
+            }
+
+            for node in stopped.iter().filter(|node| **node != target) {
+                println!("Stopped dependent {node}");
+            }
         }
         Command::Restart(_restart_command) => {
             proxy.restart(target).await.unwrap();
         }
+        Command::Reload(_reload_command) => {
+            let (status, result) = proxy.reload(target.clone()).await?;
+            if status != 0 {
+                eprintln!("Error reloading {target}: {result}");
+                std::process::exit(status as i32)
+            }
+        }
+        Command::Kill(kill_command) => {
+            let target = kill_command.target.clone().unwrap_or(target);
+            let (status, result) = proxy
+                .kill(
+                    target.clone(),
+                    kill_command.signal.clone(),
+                    kill_command.whom.clone(),
+                )
+                .await?;
+            if status != 0 {
+                eprintln!(
+                    "Error sending {} to {target}: {result}",
+                    kill_command.signal
+                );
+                std::process::exit(status as i32)
+            }
+        }
+        Command::Cat(cat_command) => {
+            let target = cat_command.target.clone().unwrap_or(target);
+            let (status, result) = proxy.cat(target.clone()).await?;
+            if status != 0 {
+                eprintln!("Error showing {target}: {result}");
+                std::process::exit(status as i32)
+            }
+
+            print!("{result}")
+        }
         Command::Start(_start_command) => {
-            proxy.start(target).await.unwrap();
+            let (status, result, stopped) = proxy.start(target.clone()).await?;
+            if status != 0 {
+                eprintln!("Error starting {target}: {result}");
+                std::process::exit(status as i32)
+            }
+
+            for node in stopped.iter() {
+                println!("Stopped conflicting {node}");
+            }
+        }
+        Command::Isolate(_isolate_command) => {
+            let (status, result, started, stopped) = proxy.isolate(target.clone()).await?;
+            if status != 0 {
+                eprintln!("Error isolating {target}: {result}");
+                std::process::exit(status as i32)
+            }
+
+            for node in stopped.iter() {
+                println!("Stopped {node}");
+            }
+
+            for node in started.iter() {
+                println!("Started {node}");
+            }
+        }
+        Command::Jobs(_jobs_command) => {
+            let (status, result) = proxy.jobs().await?;
+            if status != 0 {
+                eprintln!("Error listing jobs: {result}");
+                std::process::exit(status as i32)
+            }
+
+            println!("{result}")
+        }
+        Command::DaemonReload(_daemon_reload_command) => {
+            let (status, result, added, removed, changed, needs_restart, skipped) =
+                proxy.daemon_reload().await?;
+            if status != 0 {
+                eprintln!("Error reloading the configuration: {result}");
+                std::process::exit(status as i32)
+            }
+
+            for node in added.iter() {
+                println!("Added {node}");
+            }
+
+            for node in removed.iter() {
+                println!("Removed {node}");
+            }
+
+            for node in changed.iter() {
+                println!("Changed {node}");
+            }
+
+            for node in needs_restart.iter() {
+                println!("Changed {node}: needs restart");
+            }
+
+            for error in skipped.iter() {
+                eprintln!("Skipped {error}");
+            }
+        }
+        Command::Run(run_command) => {
+            let Some((cmd, cmd_args)) = run_command.command.split_first() else {
+                eprintln!("No command to run given");
+                std::process::exit(1)
+            };
+
+            let name = run_command
+                .name
+                .clone()
+                .unwrap_or_else(|| format!("run-{}.service", std::process::id()));
+
+            let descriptor = serde_json::json!({
+                "kind": "service",
+                "cmd": cmd,
+                "args": cmd_args,
+                "max_restarts": run_command.max_restarts,
+                "restart_delay_secs": run_command.restart_delay_secs,
+            });
+
+            let (status, result, stopped) = proxy
+                .start_transient(name.clone(), descriptor.to_string(), run_command.keep)
+                .await?;
+            if status != 0 {
+                eprintln!("Error running {name}: {result}");
+                std::process::exit(status as i32)
+            }
+
+            for node in stopped.iter() {
+                println!("Stopped conflicting {node}");
+            }
+
+            println!("Running as {name}");
+        }
+        Command::Cancel(cancel_command) => {
+            let (status, result) = proxy.cancel_job(cancel_command.id).await?;
+            if status != 0 {
+                eprintln!("Error cancelling job {}: {result}", cancel_command.id);
+                std::process::exit(status as i32)
+            }
         }
         Command::Inspect(_inspect_command) => {
             let (status, result) = proxy.inspect(target).await.unwrap();
@@ -1,3 +1,5 @@
+use std::os::unix::process::ExitStatusExt;
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Try to connect to the D-Bus session bus to check if it's available
@@ -32,5 +34,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         eprintln!("sessionrunner exited with status: {exit_status}");
     }
 
-    Ok(())
+    // pass the exit code through, following the shell convention for signals
+    let code = match (exit_status.code(), exit_status.signal()) {
+        (Some(code), _) => code,
+        (None, Some(signal)) => 128 + signal,
+        (None, None) => 1,
+    };
+
+    std::process::exit(code)
 }
@@ -17,12 +17,27 @@
     51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
 */
 
-use std::sync::Arc;
+use std::{
+    path::PathBuf,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 use serde::{Deserialize, Serialize};
-use zbus::interface;
+use tokio::sync::mpsc;
+use zbus::{interface, object_server::SignalEmitter};
 
-use crate::manager::SessionManager;
+use crate::{
+    errors::SessionManagerError,
+    job::JobKind,
+    manager::{SessionManager, StopMode},
+    node::{KillWhom, SessionNodeRun, SessionNodeSource, SessionNodeStatus, SessionNodeStopReason},
+    rusage::ResourceUsage,
+    signal::Signal,
+};
+
+/// Number of most recent runs reported by inspect.
+const INSPECT_HISTORY_LENGTH: usize = 5;
 
 #[derive(Debug, Clone)]
 pub struct SessionManagerDBus {
@@ -35,9 +50,116 @@ impl SessionManagerDBus {
     }
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct TargetRun {
+    started: u64,
+    stopped: u64,
+    reason: String,
+    usage: Option<ResourceUsage>,
+}
+
+fn unix_timestamp(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+impl From<&SessionNodeRun> for TargetRun {
+    fn from(value: &SessionNodeRun) -> Self {
+        Self {
+            started: unix_timestamp(value.started()),
+            stopped: unix_timestamp(value.stopped()),
+            reason: value.reason().to_string(),
+            usage: value.usage(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TargetSource {
+    path: String,
+    shadowed: Vec<String>,
+    dropins: Vec<String>,
+    extends: Vec<String>,
+}
+
+fn path_strings(paths: &[PathBuf]) -> Vec<String> {
+    paths
+        .iter()
+        .map(|path| path.to_string_lossy().to_string())
+        .collect()
+}
+
+impl From<&SessionNodeSource> for TargetSource {
+    fn from(value: &SessionNodeSource) -> Self {
+        Self {
+            path: value.path().to_string_lossy().to_string(),
+            shadowed: path_strings(value.shadowed()),
+            dropins: path_strings(value.dropins()),
+            extends: path_strings(value.extends()),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct TargetStatus {
+    kind: String,
     running: bool,
+    state: String,
+    /// The failed required dependency that prevents the target from running, if any
+    culprit: Option<String>,
+    /// Whether the target is running with a definition that has changed since
+    needs_restart: bool,
+    /// Whether the target has been created at runtime rather than loaded from a file
+    transient: bool,
+    source: Option<TargetSource>,
+    history: Vec<TargetRun>,
+}
+
+impl TargetStatus {
+    async fn fetch(manager: &SessionManager, target: &String) -> Result<Self, SessionManagerError> {
+        let status = manager.status(target).await?;
+        let history = manager.history(target).await?;
+        let source = manager.source(target).await?;
+
+        let culprit = match &status {
+            SessionNodeStatus::Waiting { culprit }
+            | SessionNodeStatus::Stopped {
+                reason: SessionNodeStopReason::DependencyFailed(culprit),
+                ..
+            } => Some(culprit.clone()),
+            _ => None,
+        };
+
+        let skip = history.len().saturating_sub(INSPECT_HISTORY_LENGTH);
+        Ok(Self {
+            kind: manager.kind(target).await?.to_string(),
+            running: manager.is_running(target).await?,
+            state: status.to_string(),
+            culprit,
+            needs_restart: manager.needs_restart(target).await?,
+            transient: manager.is_transient(target).await?,
+            source: source.as_ref().map(TargetSource::from),
+            history: history.iter().skip(skip).map(TargetRun::from).collect(),
+        })
+    }
+}
+
+fn error_response(err: &SessionManagerError) -> (u32, String) {
+    match err {
+        SessionManagerError::ZbusError(error) => (1, format!("{error}")),
+        SessionManagerError::NotFound(error) => (2, error.to_string()),
+        SessionManagerError::ManualActionError(error) => (3, format!("{error}")),
+        SessionManagerError::IOError(error) => (4, format!("{error}")),
+        SessionManagerError::InvalidArgument(error) => (5, error.to_string()),
+        SessionManagerError::LoadingError(error) => (6, format!("{error}")),
+        SessionManagerError::JobConflict(error) => (7, error.to_string()),
+        SessionManagerError::JobFailed(error) => (8, error.to_string()),
+        SessionManagerError::JobNotCancellable(_) => (9, format!("{err}")),
+        SessionManagerError::RequiredBy(_, _) => (10, format!("{err}")),
+        SessionManagerError::ReloadFailed(_) => (11, format!("{err}")),
+        SessionManagerError::ConflictsWithMain(_, _) => (12, format!("{err}")),
+    }
 }
 
 #[interface(
@@ -48,23 +170,132 @@ pub struct TargetStatus {
     )
 )]
 impl SessionManagerDBus {
-    pub async fn start(&self, target: String) -> u32 {
+    /// Starts the target: on success the conflicting nodes that have been stopped are returned.
+    pub async fn start(&self, target: String) -> (u32, String, Vec<String>) {
         match self.manager.start(&target).await {
-            Ok(_) => 0u32,
+            Ok(outcome) => (0, String::new(), outcome.stopped().to_vec()),
             Err(err) => {
-                eprint!("Error starting {target}: {err}");
-                todo!()
+                eprintln!("Error starting {target}: {err}");
+
+                let (status, message) = error_response(&err);
+                (status, message, vec![])
             }
         }
     }
 
-    pub async fn stop(&self, target: String) -> u32 {
-        match self.manager.stop(&target).await {
-            Ok(_) => 0u32,
+    /// Switches the session to the target, stopping every node outside of its closure:
+    /// on success the started and stopped nodes are returned, while progress is reported
+    /// through the IsolateProgress signal.
+    pub async fn isolate(
+        &self,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        target: String,
+    ) -> (u32, String, Vec<String>, Vec<String>) {
+        let (progress, mut steps) = mpsc::unbounded_channel();
+
+        let (result, _) = tokio::join!(self.manager.isolate(&target, Some(progress)), async {
+            while let Some(step) = steps.recv().await {
+                if let Err(err) = emitter
+                    .isolate_progress(&target, step.action(), step.node())
+                    .await
+                {
+                    eprintln!("Error reporting the progress of isolating {target}: {err}");
+                }
+            }
+        });
+
+        match result {
+            Ok(outcome) => (
+                0,
+                String::new(),
+                outcome.started().to_vec(),
+                outcome.stopped().to_vec(),
+            ),
+            Err(err) => {
+                eprintln!("Error isolating {target}: {err}");
+
+                let (status, message) = error_response(&err);
+                (status, message, vec![], vec![])
+            }
+        }
+    }
+
+    #[zbus(signal)]
+    async fn isolate_progress(
+        emitter: &SignalEmitter<'_>,
+        target: &str,
+        action: &str,
+        node: &str,
+    ) -> zbus::Result<()>;
+
+    /// Queues a start, stop or restart of the target without waiting for it:
+    /// on success the identifiers of the queued jobs are returned.
+    pub async fn enqueue(&self, target: String, kind: String) -> (u32, String, Vec<u64>) {
+        let result = match JobKind::try_from(kind.as_str()) {
+            Ok(kind) => self.manager.enqueue(&target, kind).await,
+            Err(err) => Err(SessionManagerError::InvalidArgument(err)),
+        };
+
+        match result {
+            Ok(ids) => {
+                let manager = self.manager.clone();
+                tokio::spawn(async move { manager.dispatch().await });
+
+                (0, String::new(), ids)
+            }
             Err(err) => {
-                eprint!("Error stopping {target}: {err}");
+                eprintln!("Error queueing {kind} of {target}: {err}");
 
-                todo!()
+                let (status, message) = error_response(&err);
+                (status, message, vec![])
+            }
+        }
+    }
+
+    /// Returns pending jobs followed by the most recently finished ones.
+    pub async fn jobs(&self) -> (u32, String) {
+        match serde_json::to_string_pretty(&self.manager.jobs()) {
+            Ok(response) => (0, response),
+            Err(err) => (4, format!("{err}")),
+        }
+    }
+
+    pub async fn job(&self, id: u64) -> (u32, String) {
+        match self.manager.job(id) {
+            Ok(job) => match serde_json::to_string_pretty(&job) {
+                Ok(response) => (0, response),
+                Err(err) => (4, format!("{err}")),
+            },
+            Err(err) => error_response(&err),
+        }
+    }
+
+    pub async fn cancel_job(&self, id: u64) -> (u32, String) {
+        match self.manager.cancel_job(id).await {
+            Ok(_) => (0, String::new()),
+            Err(err) => {
+                eprintln!("Error cancelling job {id}: {err}");
+
+                error_response(&err)
+            }
+        }
+    }
+
+    /// Stops the target: mode is either "plain", "force" or "cascade" to also stop
+    /// the nodes depending on it. On success the stopped nodes are returned.
+    pub async fn stop(&self, target: String, mode: String) -> (u32, String, Vec<String>) {
+        let result = match StopMode::try_from(mode.as_str()) {
+            Ok(mode) => self.manager.stop(&target, mode).await,
+            Err(err) => Err(SessionManagerError::InvalidArgument(err)),
+        };
+
+        match result {
+            Ok(stopped) => (0, String::new(), stopped),
+            Err(err) => {
+                eprintln!("Error stopping {target}: {err}");
+
+                let (status, message) = error_response(&err);
+                (status, message, vec![])
             }
         }
     }
@@ -73,39 +304,161 @@ impl SessionManagerDBus {
         match self.manager.restart(&target).await {
             Ok(_) => 0u32,
             Err(err) => {
-                eprint!("Error restarting {target}: {err}");
+                eprintln!("Error restarting {target}: {err}");
 
-                todo!()
+                error_response(&err).0
             }
         }
     }
 
     pub async fn inspect(&self, target: String) -> (u32, String) {
-        match self.manager.is_running(&target).await {
-            Ok(running) => {
-                let response = TargetStatus { running };
-
-                match serde_json::to_string_pretty(&response) {
-                    Ok(response) => (0, serde_json::to_string_pretty(&response).unwrap()),
-                    Err(err) => (4, format!("{err}")),
-                }
-            }
+        match TargetStatus::fetch(&self.manager, &target).await {
+            Ok(response) => match serde_json::to_string_pretty(&response) {
+                Ok(response) => (0, response),
+                Err(err) => (4, format!("{err}")),
+            },
             Err(err) => {
                 eprintln!("Error in fetching the running status of {target}: {err}");
 
-                match &err {
-                    crate::errors::SessionManagerError::ZbusError(error) => (1, format!("{error}")),
-                    crate::errors::SessionManagerError::NotFound(error) => (2, error.to_string()),
-                    crate::errors::SessionManagerError::ManualActionError(error) => {
-                        (3, format!("{error}"))
+                error_response(&err)
+            }
+        }
+    }
+
+    pub async fn cat(&self, target: String) -> (u32, String) {
+        match self.manager.source(&target).await {
+            Ok(Some(source)) => {
+                let mut response = String::new();
+
+                for path in source.shadowed().iter() {
+                    response.push_str(&format!("# {} (shadowed)\n", path.to_string_lossy()));
+                }
+
+                for path in source
+                    .extends()
+                    .iter()
+                    .chain(std::iter::once(source.path()))
+                    .chain(source.dropins().iter())
+                {
+                    response.push_str(&format!("# {}\n", path.to_string_lossy()));
+                    match std::fs::read_to_string(path) {
+                        Ok(content) => response.push_str(content.as_str()),
+                        Err(err) => return (4, format!("{err}")),
                     }
                 }
+
+                (0, response)
+            }
+            Ok(None) => (0, format!("# {target} has not been loaded from a file\n")),
+            Err(err) => {
+                eprintln!("Error fetching the source of {target}: {err}");
+
+                error_response(&err)
             }
         }
     }
 
-    pub async fn change(&self, target: String, cmd: String, args: Vec<String>) -> u32 {
-        todo!()
+    pub async fn reload(&self, target: String) -> (u32, String) {
+        match self.manager.reload(&target).await {
+            Ok(_) => (0, String::new()),
+            Err(err) => {
+                eprintln!("Error reloading {target}: {err}");
+
+                error_response(&err)
+            }
+        }
+    }
+
+    pub async fn kill(&self, target: String, signal: String, whom: String) -> (u32, String) {
+        let request = match (
+            Signal::try_from(signal.as_str()),
+            KillWhom::try_from(whom.as_str()),
+        ) {
+            (Ok(signal), Ok(whom)) => Ok((signal, whom)),
+            (Err(err), _) => Err(SessionManagerError::InvalidArgument(err.to_string())),
+            (_, Err(err)) => Err(SessionManagerError::InvalidArgument(err)),
+        };
+
+        let result = match request {
+            Ok((signal, whom)) => self.manager.kill(&target, signal, whom).await,
+            Err(err) => Err(err),
+        };
+
+        match result {
+            Ok(_) => (0, String::new()),
+            Err(err) => {
+                eprintln!("Error sending {signal} to {target}: {err}");
+
+                error_response(&err)
+            }
+        }
+    }
+
+    /// Loads the configuration again and applies the differences with the running one:
+    /// on success the added, removed, changed and needing a restart nodes are returned,
+    /// followed by the descriptors that have been skipped as those failed to load.
+    pub async fn daemon_reload(
+        &self,
+    ) -> (
+        u32,
+        String,
+        Vec<String>,
+        Vec<String>,
+        Vec<String>,
+        Vec<String>,
+        Vec<String>,
+    ) {
+        match self.manager.daemon_reload().await {
+            Ok(outcome) => (
+                0,
+                String::new(),
+                outcome.added().to_vec(),
+                outcome.removed().to_vec(),
+                outcome.changed().to_vec(),
+                outcome.needs_restart().to_vec(),
+                outcome.skipped().to_vec(),
+            ),
+            Err(err) => {
+                eprintln!("Error reloading the configuration: {err}");
+
+                let (status, message) = error_response(&err);
+                (status, message, vec![], vec![], vec![], vec![], vec![])
+            }
+        }
+    }
+
+    /// Returns whether the session has been switched to the rescue target
+    /// because the main node has failed, the rescue target and the cause.
+    pub async fn rescue_status(&self) -> (bool, String, String) {
+        match self.manager.rescued().await {
+            Some(decision) => (
+                true,
+                decision.target().to_owned(),
+                decision.cause().to_owned(),
+            ),
+            None => (false, String::new(), String::new()),
+        }
+    }
+
+    /// Creates a node from the given JSON descriptor and starts it: the node is removed
+    /// once it stops unless keep is set. On success the conflicting nodes that have been
+    /// stopped are returned, as done by start.
+    pub async fn start_transient(
+        &self,
+        name: String,
+        descriptor: String,
+        keep: bool,
+    ) -> (u32, String, Vec<String>) {
+        match SessionManager::start_transient(self.manager.clone(), &name, &descriptor, keep).await
+        {
+            Ok(outcome) => (0, String::new(), outcome.stopped().to_vec()),
+            Err(err) => {
+                eprintln!("Error starting the transient {name}: {err}");
+
+                let (status, message) = error_response(&err);
+                (status, message, vec![])
+            }
+        }
     }
 
     pub async fn terminate(&self) -> u32 {
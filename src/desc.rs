@@ -18,33 +18,186 @@
 */
 
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, BTreeSet, HashMap},
     fs::File,
     io::Read,
-    path::PathBuf,
+    path::{Path, PathBuf},
     sync::Arc,
     time::Duration,
 };
 
 use crate::signal::Signal;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
 use crate::{
     errors::{NodeLoadingError, NodeLoadingResult},
-    node::{SessionNode, SessionNodeRestart},
+    node::{
+        DependencyFailurePolicy, SessionNode, SessionNodeCommand, SessionNodeDependencies,
+        SessionNodeHooks, SessionNodeReload, SessionNodeRestart, SessionNodeSource,
+        SessionNodeStop, SessionNodeType,
+    },
 };
 
+/// A command executed as a hook of a service.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NodeCommandDescriptor {
+    cmd: String,
+    #[serde(default)]
+    args: Vec<String>,
+}
+
+impl From<&NodeCommandDescriptor> for SessionNodeCommand {
+    fn from(value: &NodeCommandDescriptor) -> Self {
+        SessionNodeCommand::new(value.cmd.clone(), value.args.clone())
+    }
+}
+
+/// Values substituted to `%` specifiers in descriptors:
+/// - `%n`: the full name of the service, for example `foo@bar.service`;
+/// - `%p`: the name of the service without the instance and suffix, for example `foo`;
+/// - `%i`: the instance name of a templated service, for example `bar`;
+/// - `%h`: the home directory of the user;
+/// - `%t`: the runtime directory of the user;
+/// - `%%`: a literal `%`.
+///
+/// Specifiers are substituted in `cmd`, `args`, `environment` values and `pidfile`.
+struct NodeSpecifiers {
+    name: String,
+    prefix: String,
+    instance: Option<String>,
+    suffix: String,
+}
+
+impl NodeSpecifiers {
+    fn new(name: &str) -> Self {
+        let (stem, suffix) = match name.rfind('.') {
+            Some(position) => (&name[..position], &name[position..]),
+            None => (name, ""),
+        };
+
+        let (prefix, instance) = match stem.split_once('@') {
+            Some((prefix, instance)) => (prefix, Some(String::from(instance))),
+            None => (stem, None),
+        };
+
+        Self {
+            name: String::from(name),
+            prefix: String::from(prefix),
+            instance,
+            suffix: String::from(suffix),
+        }
+    }
+
+    /// Returns the name of the template file `foo@.service` for an instance `foo@bar.service`.
+    fn template(&self) -> Option<String> {
+        match &self.instance {
+            Some(instance) if !instance.is_empty() => {
+                Some(format!("{}@{}", self.prefix, self.suffix))
+            }
+            _ => None,
+        }
+    }
+
+    fn expand(&self, value: &str) -> String {
+        let mut result = String::with_capacity(value.len());
+        let mut chars = value.chars();
+
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                result.push(c);
+                continue;
+            }
+
+            match chars.next() {
+                Some('n') => result.push_str(&self.name),
+                Some('p') => result.push_str(&self.prefix),
+                Some('i') => result.push_str(self.instance.as_deref().unwrap_or_default()),
+                Some('h') => result.push_str(&std::env::var("HOME").unwrap_or_default()),
+                Some('t') => result.push_str(&std::env::var("XDG_RUNTIME_DIR").unwrap_or_default()),
+                Some('%') => result.push('%'),
+                Some(other) => {
+                    result.push('%');
+                    result.push(other);
+                }
+                None => result.push('%'),
+            }
+        }
+
+        result
+    }
+
+    fn apply(&self, descriptor: &mut Value) {
+        let Value::Object(fields) = descriptor else {
+            return;
+        };
+
+        for (key, value) in fields.iter_mut() {
+            match (key.as_str(), value) {
+                ("cmd" | "pidfile", Value::String(value)) => *value = self.expand(value),
+                ("args", Value::Array(args)) => args.iter_mut().for_each(|arg| {
+                    if let Value::String(arg) = arg {
+                        *arg = self.expand(arg)
+                    }
+                }),
+                ("environment", Value::Object(environment)) => {
+                    environment.values_mut().for_each(|val| {
+                        if let Value::String(val) = val {
+                            *val = self.expand(val)
+                        }
+                    })
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Extensions of the files holding node descriptors.
+const DESCRIPTOR_EXTENSIONS: [&str; 2] = ["service", "target"];
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct NodeServiceDescriptor {
     kind: String,
     pidfile: Option<PathBuf>,
+    #[serde(default)]
     cmd: String,
     stop_signal: Option<String>,
+    exec_stop: Option<NodeCommandDescriptor>,
+    stop_timeout_secs: Option<u64>,
+    reload_signal: Option<String>,
+    exec_reload: Option<NodeCommandDescriptor>,
+    #[serde(default)]
     args: Vec<String>,
+    #[serde(default)]
     max_restarts: u64,
+    #[serde(default)]
     restart_delay_secs: u64,
+    #[serde(default)]
     dependencies: Vec<String>,
+    #[serde(default)]
+    requires: Vec<String>,
+    #[serde(default)]
+    wants: Vec<String>,
+    #[serde(default)]
+    after: Vec<String>,
+    #[serde(default)]
+    binds_to: Vec<String>,
+    #[serde(default)]
+    part_of: Vec<String>,
+    #[serde(default)]
+    conflicts: Vec<String>,
+    #[serde(default)]
+    on_failure: Vec<String>,
+    on_dependency_failure: Option<String>,
     environment: Option<HashMap<String, String>>,
+    #[serde(default)]
+    exec_start_pre: Vec<NodeCommandDescriptor>,
+    #[serde(default)]
+    exec_start_post: Vec<NodeCommandDescriptor>,
+    #[serde(default)]
+    exec_stop_post: Vec<NodeCommandDescriptor>,
+    extends: Option<String>,
 }
 
 impl NodeServiceDescriptor {
@@ -53,11 +206,61 @@ impl NodeServiceDescriptor {
         filename: &String,
         directories: &[PathBuf],
     ) -> NodeLoadingResult<()> {
-        let mut currently_loading = HashSet::new();
+        let mut currently_loading = Vec::new();
 
         Self::find_and_load(hashmap, filename, directories, &mut currently_loading).await
     }
 
+    /// Loads every service and target descriptor found in `directories` (together with its
+    /// dependencies) so that nodes not reachable from the default target can still be started
+    /// on demand.
+    ///
+    /// Templates (`name@.service`, `name@.target`) are skipped as they are only instantiated
+    /// when requested.
+    /// A descriptor failing to load does not prevent the others from being loaded:
+    /// every failure is returned alongside the name of the file that caused it.
+    pub async fn load_all(
+        hashmap: &mut HashMap<String, Arc<SessionNode>>,
+        directories: &[PathBuf],
+    ) -> Vec<(String, NodeLoadingError)> {
+        let mut errors = Vec::new();
+        let mut filenames = BTreeSet::new();
+
+        for directory in directories.iter() {
+            let entries = match std::fs::read_dir(directory) {
+                Ok(entries) => entries,
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(err) => {
+                    errors.push((directory.to_string_lossy().to_string(), err.into()));
+                    continue;
+                }
+            };
+
+            for entry in entries.flatten() {
+                let filename = entry.file_name().to_string_lossy().to_string();
+                let Some((stem, extension)) = filename.rsplit_once('.') else {
+                    continue;
+                };
+
+                if !DESCRIPTOR_EXTENSIONS.contains(&extension) || stem.ends_with('@') {
+                    continue;
+                }
+
+                if entry.path().is_file() {
+                    filenames.insert(filename);
+                }
+            }
+        }
+
+        for filename in filenames.iter() {
+            if let Err(err) = Self::load_tree(hashmap, filename, directories).await {
+                errors.push((filename.clone(), err));
+            }
+        }
+
+        errors
+    }
+
     /// Attempts to find and load a session node from a specified file, checking for cyclic dependencies.
     ///
     /// This function searches for a file with the given `filename` in the provided `directories`.
@@ -66,21 +269,28 @@ impl NodeServiceDescriptor {
     /// `hashmap`. The function also recursively loads any dependencies specified in the
     /// `NodeServiceDescriptor`.
     ///
+    /// Dependencies form a directed acyclic graph: a node that has already been loaded
+    /// (for example because two services depend on the same one) is reused as is,
+    /// while a node that is found again while its own dependencies are being loaded
+    /// closes a cycle and is reported as an error.
+    ///
     /// # Parameters
     ///
     /// - `hashmap`: A mutable reference to a `HashMap` that stores loaded session nodes. The keys
     ///   are filenames, and the values are `Arc<RwLock<SessionNode>>` instances representing the
     ///   loaded nodes.
     /// - `filename`: A reference to a `String` that specifies the name of the file to load.
-    /// - `directories`: A slice of `PathBuf` representing the directories to search for the file.
-    /// - `currently_loading`: A mutable reference to a `HashSet<String>` that tracks the filenames
-    ///   currently being loaded to detect cyclic dependencies.
+    /// - `directories`: A slice of `PathBuf` representing the directories to search for the file,
+    ///   sorted by decreasing priority: the first directory containing the file is the one used.
+    /// - `currently_loading`: A mutable reference to a `Vec<String>` that tracks the chain of
+    ///   filenames currently being loaded to detect cyclic dependencies.
     ///
     /// # Returns
     ///
     /// This function returns a `NodeLoadingResult<()>`. On success, it returns `Ok(())`. If an error
     /// occurs, it returns a `NodeLoadingError` variant, which can indicate issues such as:
-    /// - `CyclicDependency`: If a cyclic dependency is detected during loading.
+    /// - `CyclicDependency`: If a cyclic dependency is detected during loading: the error
+    ///   carries the whole cycle, for example `a -> b -> c -> a`.
     /// - `FileNotFound`: If the specified file cannot be found in the provided directories.
     /// - `IOError`: If an I/O error occurs while opening or reading the file.
     /// - `JSONError`: If the file contents cannot be deserialized into a `NodeServiceDescriptor`.
@@ -88,58 +298,270 @@ impl NodeServiceDescriptor {
     /// # Safety
     ///
     /// This function is not `unsafe`, but care should be taken to ensure that the `currently_loading`
-    /// set is properly managed to avoid memory leaks or deadlocks in a multi-threaded context.
+    /// chain is properly managed to avoid memory leaks or deadlocks in a multi-threaded context.
     async fn find_and_load(
         hashmap: &mut HashMap<String, Arc<SessionNode>>,
         filename: &String,
         directories: &[PathBuf],
-        currently_loading: &mut HashSet<String>,
+        currently_loading: &mut Vec<String>,
     ) -> NodeLoadingResult<()> {
-        // Check for cyclic dependency
-        if currently_loading.contains(filename) {
-            return Err(NodeLoadingError::CyclicDependency(filename.clone()));
-        }
+        // Check for cyclic dependency: a back-edge to a node whose dependencies are being loaded
+        if let Some(position) = currently_loading.iter().position(|name| name == filename) {
+            let cycle = currently_loading[position..]
+                .iter()
+                .chain(std::iter::once(filename))
+                .cloned()
+                .collect::<Vec<_>>();
 
-        // Add the current filename to the loading set
-        currently_loading.insert(filename.clone());
+            return Err(NodeLoadingError::CyclicDependency(cycle.join(" -> ")));
+        }
 
-        // Check if the file is already loaded
+        // Check if the file is already loaded: shared dependencies are reused
         if hashmap.contains_key(filename) {
-            // Remove from loading set before returning
-            currently_loading.remove(filename);
-            return Err(NodeLoadingError::CyclicDependency(filename.clone()));
+            return Ok(());
         }
 
-        let mut chosen = None;
+        // Add the current filename to the loading chain
+        currently_loading.push(filename.clone());
 
-        for dir in directories.iter() {
-            let file = dir.join(filename);
-            if file.exists() {
-                chosen = Some(file);
+        let specifiers = NodeSpecifiers::new(filename);
+
+        // instances of a template without a dedicated file are loaded from the template
+        let (mut chosen, mut shadowed) = Self::find_file(filename, directories);
+        let mut dropins = vec![];
+        if let Some(template) = specifiers.template() {
+            if chosen.is_none() {
+                (chosen, shadowed) = Self::find_file(&template, directories);
             }
+
+            dropins.extend(Self::find_dropins(&template, directories)?);
         }
 
-        let value = match chosen {
-            Some(filepath) => {
-                let mut file = File::open(filepath).map_err(NodeLoadingError::IOError)?;
-                let mut value = String::new();
-                file.read_to_string(&mut value)
-                    .map_err(NodeLoadingError::IOError)?;
-                value
-            }
-            None => {
-                currently_loading.remove(filename); // Clean up before returning
-                return Err(NodeLoadingError::FileNotFound(filename.clone()));
-            }
+        let Some(filepath) = chosen else {
+            currently_loading.pop(); // Clean up before returning
+            return Err(NodeLoadingError::FileNotFound(filename.clone()));
         };
 
-        let main = serde_json::from_str::<NodeServiceDescriptor>(value.as_str())
+        // Resolve the descriptors this one extends (if any) and apply drop-in overrides
+        // on top of the resulting descriptor: the ones of the template first,
+        // then the ones of the instance.
+        let mut extended = vec![];
+        let mut inherited_dropins = vec![];
+        let mut extending = vec![filepath
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default()];
+        let mut value = Self::resolve_extends(
+            &filepath,
+            directories,
+            &mut extending,
+            &mut extended,
+            &mut inherited_dropins,
+        )?;
+
+        dropins.extend(Self::find_dropins(filename, directories)?);
+        for dropin in dropins.iter() {
+            Self::merge_override(&mut value, Self::read_json(dropin)?, || {
+                NodeLoadingError::InvalidDropIn(dropin.to_string_lossy().to_string())
+            })?;
+        }
+
+        specifiers.apply(&mut value);
+
+        inherited_dropins.extend(dropins);
+        let source = SessionNodeSource::new(
+            filepath,
+            shadowed,
+            inherited_dropins,
+            extended,
+            value.clone(),
+        );
+
+        Self::load_value(
+            hashmap,
+            filename,
+            value,
+            Some(source),
+            directories,
+            currently_loading,
+        )
+        .await?;
+
+        // Remove the filename from the loading chain after processing
+        currently_loading.pop();
+
+        Ok(())
+    }
+
+    /// Loads a node that has no file of its own from the given JSON descriptor,
+    /// as done for nodes created at runtime: its dependencies are looked up in
+    /// `directories` exactly as the ones of any other node.
+    ///
+    /// Transient descriptors cannot extend other descriptors, and drop-in overrides
+    /// are not applied to them.
+    pub async fn load_transient(
+        hashmap: &mut HashMap<String, Arc<SessionNode>>,
+        filename: &String,
+        descriptor: &str,
+        directories: &[PathBuf],
+    ) -> NodeLoadingResult<()> {
+        let value = serde_json::from_str::<Value>(descriptor)?;
+        if value.get("extends").is_some() {
+            return Err(NodeLoadingError::InvalidExtends(filename.clone()));
+        }
+
+        let mut currently_loading = vec![filename.clone()];
+
+        Self::load_value(
+            hashmap,
+            filename,
+            value,
+            None,
+            directories,
+            &mut currently_loading,
+        )
+        .await
+    }
+
+    /// Creates the node described by `value` (loading its dependencies)
+    /// and inserts it into `hashmap`.
+    async fn load_value(
+        hashmap: &mut HashMap<String, Arc<SessionNode>>,
+        filename: &String,
+        value: Value,
+        source: Option<SessionNodeSource>,
+        directories: &[PathBuf],
+        currently_loading: &mut Vec<String>,
+    ) -> NodeLoadingResult<()> {
+        let main = serde_json::from_value::<NodeServiceDescriptor>(value)
             .map_err(NodeLoadingError::JSONError)?;
 
-        let mut dependencies = vec![];
+        // Parse all dependencies and then register those as part of node:
+        // the legacy dependencies list means both required and ordered after
+        let requires = Self::load_dependencies(
+            hashmap,
+            main.dependencies().iter().chain(main.requires().iter()),
+            directories,
+            currently_loading,
+        )
+        .await?;
+        let wants =
+            Self::load_dependencies(hashmap, main.wants().iter(), directories, currently_loading)
+                .await?;
+        let after = Self::load_dependencies(
+            hashmap,
+            main.dependencies().iter().chain(main.after().iter()),
+            directories,
+            currently_loading,
+        )
+        .await?;
+        let binds_to = Self::load_dependencies(
+            hashmap,
+            main.binds_to().iter(),
+            directories,
+            currently_loading,
+        )
+        .await?;
+
+        let stop_signal = match &main.stop_signal {
+            Some(sig) => Self::parse_signal(sig)?,
+            None => Signal::SIGTERM,
+        };
+
+        let reload_signal = match &main.reload_signal {
+            Some(sig) => Some(Self::parse_signal(sig)?),
+            None => None,
+        };
+
+        let kind = match main.kind.as_str() {
+            "service" => SessionNodeType::Service,
+            "oneshot" => SessionNodeType::OneShot,
+            "target" => SessionNodeType::Target,
+            _ => return Err(NodeLoadingError::InvalidKind(main.kind.clone())),
+        };
+
+        let on_dependency_failure = match &main.on_dependency_failure {
+            Some(policy) => DependencyFailurePolicy::try_from(policy.as_str())
+                .map_err(|_| NodeLoadingError::InvalidPolicy(policy.clone()))?,
+            None => DependencyFailurePolicy::default(),
+        };
+
+        // targets have no process of their own: every other kind needs one
+        if (kind == SessionNodeType::Target) != main.cmd.is_empty() {
+            return Err(NodeLoadingError::InvalidCommand(filename.clone()));
+        }
+
+        let node = SessionNode::new(
+            filename.clone(),
+            kind,
+            main.pidfile(),
+            main.cmd(),
+            main.args(),
+            SessionNodeStop::new(
+                stop_signal,
+                main.exec_stop.as_ref().map(SessionNodeCommand::from),
+                main.stop_timeout(),
+            ),
+            SessionNodeRestart::new(main.max_restarts(), main.delay()),
+            SessionNodeDependencies::new(
+                requires,
+                wants,
+                after,
+                binds_to,
+                main.part_of().to_vec(),
+                main.conflicts().to_vec(),
+                main.on_failure().to_vec(),
+                on_dependency_failure,
+            ),
+            main.environment.clone().unwrap_or_default(),
+            main.hooks(),
+            SessionNodeReload::new(
+                reload_signal,
+                main.exec_reload.as_ref().map(SessionNodeCommand::from),
+            ),
+            source,
+        );
+
+        // keep reverse edges so that a node can reach the ones depending on it
+        let node = Arc::new(node);
+        for dependency in node.dependencies().all() {
+            dependency.add_dependent(&node);
+        }
+
+        // part_of is by name: the reverse edge is added by whichever side is loaded last
+        for other in hashmap.values() {
+            if node
+                .dependencies()
+                .part_of()
+                .contains(&other.name().to_owned())
+            {
+                other.add_dependent(&node);
+            }
+
+            if other.dependencies().part_of().contains(filename) {
+                node.add_dependent(other);
+            }
+        }
+
+        hashmap.insert(filename.clone(), node);
+
+        Ok(())
+    }
+
+    /// Loads the given dependencies (each one only once) returning the corresponding nodes.
+    async fn load_dependencies(
+        hashmap: &mut HashMap<String, Arc<SessionNode>>,
+        names: impl Iterator<Item = &String>,
+        directories: &[PathBuf],
+        currently_loading: &mut Vec<String>,
+    ) -> NodeLoadingResult<Vec<Arc<SessionNode>>> {
+        let mut dependencies: Vec<Arc<SessionNode>> = vec![];
+
+        for dep in names {
+            if dependencies.iter().any(|loaded| loaded.name() == dep) {
+                continue;
+            }
 
-        // Parse all dependencies and then register those as part of node
-        for dep in main.dependencies().iter() {
             Box::pin(Self::find_and_load(
                 hashmap,
                 dep,
@@ -152,66 +574,173 @@ impl NodeServiceDescriptor {
             dependencies.push(just_loaded.clone());
         }
 
-        let stop_signal = match &main.stop_signal {
-            Some(sig) => match sig.to_ascii_uppercase().as_str() {
-                "SIGABRT" => Signal::SIGABRT,
-                "SIGABORT" => Signal::SIGABRT,
-                "SIGALRM" => Signal::SIGALRM,
-                "SIGBUS" => Signal::SIGBUS,
-                "SIGCHLD" => Signal::SIGCHLD,
-                "SIGCLD" => Signal::SIGCHLD,
-                "SIGCONT" => Signal::SIGCONT,
-                "SIGFPE" => Signal::SIGFPE,
-                "SIGHUP" => Signal::SIGHUP,
-                "SIGILL" => Signal::SIGILL,
-                "SIGINT" => Signal::SIGINT,
-                "SIGKILL" => Signal::SIGKILL,
-                "SIGPIPE" => Signal::SIGPIPE,
-                "SIGTERM" => Signal::SIGTERM,
-                "SIGQUIT" => Signal::SIGQUIT,
-                "SIGSTOP" => Signal::SIGSTOP,
-                "SIGTSTP" => Signal::SIGTSTP,
-                "SIGTRAP" => Signal::SIGTRAP,
-                "SIGTTIN" => Signal::SIGTTIN,
-                "SIGTTOU" => Signal::SIGTTOU,
-                "SIGURG" => Signal::SIGURG,
-                "SIGUSR1" => Signal::SIGUSR1,
-                "SIGUSR2" => Signal::SIGUSR2,
-                "SIGVTALRM" => Signal::SIGVTALRM,
-                "SIGXCPU" => Signal::SIGXCPU,
-                "SIGXFSZ" => Signal::SIGXFSZ,
-                _ => panic!("unrecognised!"),
-            },
-            None => Signal::SIGTERM,
+        Ok(dependencies)
+    }
+
+    /// Searches `filename` in `directories`, returning the file to be used and the ones it shadows.
+    ///
+    /// Directories are sorted from the highest to the lowest priority:
+    /// the first one containing the file wins and shadows the others.
+    fn find_file(filename: &str, directories: &[PathBuf]) -> (Option<PathBuf>, Vec<PathBuf>) {
+        let mut candidates = directories
+            .iter()
+            .map(|dir| dir.join(filename))
+            .filter(|file| file.exists());
+        let chosen = candidates.next();
+        let shadowed = candidates.collect::<Vec<_>>();
+
+        (chosen, shadowed)
+    }
+
+    fn read_json(path: &Path) -> NodeLoadingResult<Value> {
+        let mut file = File::open(path).map_err(NodeLoadingError::IOError)?;
+        let mut value = String::new();
+        file.read_to_string(&mut value)
+            .map_err(NodeLoadingError::IOError)?;
+
+        serde_json::from_str::<Value>(value.as_str()).map_err(NodeLoadingError::JSONError)
+    }
+
+    /// Collects the drop-in overrides for `filename`: every `*.json` file inside a
+    /// `<filename>.d` directory of any of the load directories.
+    ///
+    /// Drop-ins are sorted by their file name: a drop-in in a higher-priority directory
+    /// shadows those with the same file name in lower-priority directories.
+    fn find_dropins(filename: &str, directories: &[PathBuf]) -> NodeLoadingResult<Vec<PathBuf>> {
+        let mut dropins = BTreeMap::new();
+
+        for dir in directories.iter() {
+            let dropin_dir = dir.join(format!("{filename}.d"));
+            if !dropin_dir.is_dir() {
+                continue;
+            }
+
+            for entry in std::fs::read_dir(&dropin_dir).map_err(NodeLoadingError::IOError)? {
+                let path = entry.map_err(NodeLoadingError::IOError)?.path();
+                if path.extension().is_some_and(|ext| ext == "json") {
+                    if let Some(name) = path.file_name() {
+                        dropins.entry(name.to_os_string()).or_insert(path);
+                    }
+                }
+            }
+        }
+
+        Ok(dropins.into_values().collect())
+    }
+
+    /// Loads the descriptor at `path` resolving its `extends` field: the extended descriptor
+    /// is looked up in `directories`, loaded (recursively) with its own drop-in overrides
+    /// applied, and then the descriptor at `path` is merged over it with the same rules
+    /// used for drop-in overrides.
+    ///
+    /// `extending` is the chain of descriptors being resolved, used to detect cycles, while
+    /// `extended` and `dropins` collect the files of extended descriptors and their drop-ins.
+    fn resolve_extends(
+        path: &Path,
+        directories: &[PathBuf],
+        extending: &mut Vec<String>,
+        extended: &mut Vec<PathBuf>,
+        dropins: &mut Vec<PathBuf>,
+    ) -> NodeLoadingResult<Value> {
+        let value = Self::read_json(path)?;
+
+        let parent = match value.get("extends") {
+            None => return Ok(value),
+            Some(Value::String(parent)) => parent.clone(),
+            Some(_) => {
+                return Err(NodeLoadingError::InvalidExtends(
+                    path.to_string_lossy().to_string(),
+                ))
+            }
         };
 
-        let node = SessionNode::new(
-            filename.clone(),
-            match main.kind.as_str() {
-                "service" => crate::node::SessionNodeType::Service,
-                "oneshot" => crate::node::SessionNodeType::OneShot,
-                _ => return Err(NodeLoadingError::InvalidKind(main.kind.clone())),
-            },
-            main.pidfile(),
-            main.cmd(),
-            main.args(),
-            stop_signal,
-            SessionNodeRestart::new(main.max_restarts(), main.delay()),
-            dependencies,
-            match main.environment {
-                Some(env) => env,
-                None => HashMap::new(),
-            },
-        );
+        if let Some(position) = extending.iter().position(|name| *name == parent) {
+            let cycle = extending[position..]
+                .iter()
+                .chain(std::iter::once(&parent))
+                .cloned()
+                .collect::<Vec<_>>();
+
+            return Err(NodeLoadingError::CyclicExtends(cycle.join(" -> ")));
+        }
+
+        let (Some(parent_path), _) = Self::find_file(&parent, directories) else {
+            return Err(NodeLoadingError::FileNotFound(parent));
+        };
+
+        extending.push(parent.clone());
+        let mut base =
+            Self::resolve_extends(&parent_path, directories, extending, extended, dropins)?;
+        extending.pop();
 
-        hashmap.insert(filename.clone(), Arc::new(node));
+        let parent_dropins = Self::find_dropins(&parent, directories)?;
+        for dropin in parent_dropins.iter() {
+            Self::merge_override(&mut base, Self::read_json(dropin)?, || {
+                NodeLoadingError::InvalidDropIn(dropin.to_string_lossy().to_string())
+            })?;
+        }
+
+        extended.push(parent_path);
+        dropins.extend(parent_dropins);
+
+        Self::merge_override(&mut base, value, || {
+            NodeLoadingError::InvalidExtends(path.to_string_lossy().to_string())
+        })?;
+
+        Ok(base)
+    }
+
+    /// Merges an override (a drop-in or a descriptor extending another one) into a descriptor:
+    /// - scalar fields are replaced by the override value;
+    /// - `environment` is merged key by key;
+    /// - list fields are reset to the override value when written as `"field": [...]`
+    ///   and extended with the override value when written as `"field+": [...]`.
+    fn merge_override(
+        base: &mut Value,
+        overrides: Value,
+        invalid: impl Fn() -> NodeLoadingError,
+    ) -> NodeLoadingResult<()> {
+        let (Value::Object(base), Value::Object(overrides)) = (base, overrides) else {
+            return Err(invalid());
+        };
+
+        for (key, value) in overrides.into_iter() {
+            if let Some(field) = key.strip_suffix('+') {
+                let Value::Array(items) = value else {
+                    return Err(invalid());
+                };
+
+                match base.entry(field).or_insert_with(|| Value::Array(vec![])) {
+                    Value::Array(list) => list.extend(items),
+                    _ => return Err(invalid()),
+                }
+
+                continue;
+            }
 
-        // Remove the filename from the loading set after processing
-        currently_loading.remove(filename);
+            match (key.as_str(), base.get_mut(key.as_str()), value) {
+                ("environment", Some(Value::Object(environment)), Value::Object(overrides)) => {
+                    environment.extend(overrides)
+                }
+                (_, _, value) => {
+                    base.insert(key, value);
+                }
+            }
+        }
 
         Ok(())
     }
 
+    fn parse_signal(name: &str) -> NodeLoadingResult<Signal> {
+        match name.to_ascii_uppercase().as_str() {
+            // aliases descriptors have always been allowed to use
+            "SIGABORT" => Ok(Signal::SIGABRT),
+            "SIGCLD" => Ok(Signal::SIGCHLD),
+            name_upper => Signal::try_from(name_upper)
+                .map_err(|_| NodeLoadingError::InvalidSignal(String::from(name))),
+        }
+    }
+
     pub fn pidfile(&self) -> Option<PathBuf> {
         self.pidfile.clone()
     }
@@ -232,7 +761,59 @@ impl NodeServiceDescriptor {
         Duration::from_secs(self.restart_delay_secs)
     }
 
+    pub fn stop_timeout(&self) -> Duration {
+        match self.stop_timeout_secs {
+            Some(secs) => Duration::from_secs(secs),
+            None => SessionNodeStop::DEFAULT_TIMEOUT,
+        }
+    }
+
     pub fn dependencies(&self) -> &[String] {
         self.dependencies.as_slice()
     }
+
+    pub fn requires(&self) -> &[String] {
+        self.requires.as_slice()
+    }
+
+    pub fn wants(&self) -> &[String] {
+        self.wants.as_slice()
+    }
+
+    pub fn after(&self) -> &[String] {
+        self.after.as_slice()
+    }
+
+    pub fn binds_to(&self) -> &[String] {
+        self.binds_to.as_slice()
+    }
+
+    pub fn part_of(&self) -> &[String] {
+        self.part_of.as_slice()
+    }
+
+    pub fn conflicts(&self) -> &[String] {
+        self.conflicts.as_slice()
+    }
+
+    pub fn on_failure(&self) -> &[String] {
+        self.on_failure.as_slice()
+    }
+
+    pub fn hooks(&self) -> SessionNodeHooks {
+        SessionNodeHooks::new(
+            self.exec_start_pre
+                .iter()
+                .map(SessionNodeCommand::from)
+                .collect(),
+            self.exec_start_post
+                .iter()
+                .map(SessionNodeCommand::from)
+                .collect(),
+            self.exec_stop_post
+                .iter()
+                .map(SessionNodeCommand::from)
+                .collect(),
+        )
+    }
 }
@@ -18,7 +18,7 @@
 */
 
 use serde_json::error::Error as JSONError;
-use std::io::Error as IOError;
+use std::{io::Error as IOError, process::ExitStatus};
 use thiserror::Error;
 use zbus::Error as ZError;
 
@@ -34,6 +34,33 @@ pub enum SessionManagerError {
 
     #[error("Error issuing manual action: {0}")]
     ManualActionError(#[from] ManualActionIssueError),
+
+    #[error("Invalid argument: {0}")]
+    InvalidArgument(String),
+
+    #[error("Error loading the service: {0}")]
+    LoadingError(#[from] NodeLoadingError),
+
+    #[error("Job conflicts with a pending one: {0}")]
+    JobConflict(String),
+
+    #[error("Job failed: {0}")]
+    JobFailed(String),
+
+    #[error("Job {0} is not waiting and cannot be cancelled")]
+    JobNotCancellable(u64),
+
+    #[error("{0} conflicts with the main node {1}")]
+    ConflictsWithMain(String, String),
+
+    #[error("{0} is required by running nodes: {1}")]
+    RequiredBy(String, String),
+
+    #[error("I/O error: {0}")]
+    IOError(#[from] IOError),
+
+    #[error("Error reloading the configuration, nothing has been changed: {0}")]
+    ReloadFailed(String),
 }
 
 #[derive(Debug, Error)]
@@ -52,6 +79,24 @@ pub enum NodeLoadingError {
 
     #[error("Invalid service kind: {0}")]
     InvalidKind(String),
+
+    #[error("Invalid signal name: {0}")]
+    InvalidSignal(String),
+
+    #[error("Invalid drop-in override: {0}")]
+    InvalidDropIn(String),
+
+    #[error("Invalid extends field or extended descriptor: {0}")]
+    InvalidExtends(String),
+
+    #[error("Cyclic inheritance found: {0}")]
+    CyclicExtends(String),
+
+    #[error("Missing command, or command given to a target: {0}")]
+    InvalidCommand(String),
+
+    #[error("Invalid dependency failure policy: {0}")]
+    InvalidPolicy(String),
 }
 
 pub type NodeLoadingResult<T> = Result<T, NodeLoadingError>;
@@ -66,3 +111,12 @@ pub(crate) enum NodeDependencyError {
 }
 
 pub(crate) type NodeDependencyResult<T> = Result<T, NodeDependencyError>;
+
+#[derive(Debug, Error)]
+pub(crate) enum NodeHookError {
+    #[error("I/O error: {0}")]
+    IOError(#[from] IOError),
+
+    #[error("{0} terminated with {1}")]
+    Failed(String, ExitStatus),
+}
@@ -0,0 +1,318 @@
+/*
+    login-ng A greeter written in rust that also supports autologin with systemd-homed
+    Copyright (C) 2024-2025  Denis Benato
+
+    This program is free software; you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation; either version 2 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License along
+    with this program; if not, write to the Free Software Foundation, Inc.,
+    51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
+*/
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+};
+
+use serde::{Deserialize, Serialize};
+
+/// Number of finished jobs that can still be queried.
+const FINISHED_JOBS_LENGTH: usize = 32;
+
+pub type JobId = u64;
+
+/// Identifies the transaction a job has been queued by.
+pub type TransactionId = u64;
+
+/// What a job does to its node.
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Debug)]
+pub enum JobKind {
+    Start,
+    Stop,
+    Restart,
+}
+
+impl JobKind {
+    /// Returns the job that performs both `self` and `other` on the same node, if any:
+    /// a restart also starts a stopped node, while stopping is incompatible with both.
+    pub fn merge(self, other: JobKind) -> Option<JobKind> {
+        match (self, other) {
+            (a, b) if a == b => Some(a),
+            (JobKind::Start, JobKind::Restart) | (JobKind::Restart, JobKind::Start) => {
+                Some(JobKind::Restart)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for JobKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JobKind::Start => write!(f, "start"),
+            JobKind::Stop => write!(f, "stop"),
+            JobKind::Restart => write!(f, "restart"),
+        }
+    }
+}
+
+impl TryFrom<&str> for JobKind {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "start" => Ok(JobKind::Start),
+            "stop" => Ok(JobKind::Stop),
+            "restart" => Ok(JobKind::Restart),
+            _ => Err(format!("Invalid job kind: {value}")),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub enum JobState {
+    Waiting,
+    Running,
+    /// The job has changed the state of its node
+    Done,
+    /// The node was already in the requested state
+    Redundant,
+    Failed(String),
+    Cancelled,
+}
+
+impl JobState {
+    pub fn is_finished(&self) -> bool {
+        !matches!(self, JobState::Waiting | JobState::Running)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Job {
+    id: JobId,
+    node: String,
+    kind: JobKind,
+    state: JobState,
+    /// Transactions the job is part of: more than one once jobs have been merged
+    #[serde(skip)]
+    transactions: Vec<TransactionId>,
+}
+
+impl Job {
+    pub fn id(&self) -> JobId {
+        self.id
+    }
+
+    pub fn node(&self) -> &str {
+        self.node.as_str()
+    }
+
+    pub fn kind(&self) -> JobKind {
+        self.kind
+    }
+
+    pub fn state(&self) -> &JobState {
+        &self.state
+    }
+}
+
+/// Jobs waiting to be run (or running) in the order they have been queued,
+/// followed by the most recently finished ones.
+///
+/// Finished jobs of awaited transactions are kept until released, even if
+/// more than `FINISHED_JOBS_LENGTH` jobs have finished in the meantime.
+#[derive(Debug, Default)]
+pub(crate) struct JobQueue {
+    next_id: JobId,
+    next_transaction: TransactionId,
+    active: VecDeque<Job>,
+    finished: VecDeque<Job>,
+    awaited: HashMap<JobId, usize>,
+}
+
+impl JobQueue {
+    /// Queues every job of a transaction, or none of them if any conflicts with a pending job:
+    /// when `awaited` is set the jobs are kept once finished until passed to `release`.
+    ///
+    /// A job compatible with a waiting job on the same node is merged into it, as long as
+    /// that job runs after every earlier job of the transaction: the returned identifiers
+    /// (one for each job of the transaction) can therefore be shared.
+    pub(crate) fn add_transaction(
+        &mut self,
+        transaction: &[(String, JobKind)],
+        awaited: bool,
+    ) -> Result<Vec<JobId>, String> {
+        for (node, kind) in transaction.iter() {
+            if let Some(job) = self
+                .active
+                .iter()
+                .find(|job| job.node == *node && kind.merge(job.kind).is_none())
+            {
+                return Err(format!(
+                    "{kind} of {node} conflicts with job {} ({} of {node})",
+                    job.id, job.kind
+                ));
+            }
+        }
+
+        self.next_transaction += 1;
+        let transaction_id = self.next_transaction;
+
+        let mut ids = vec![];
+        let mut earliest = 0;
+        for (node, kind) in transaction.iter() {
+            let position = match self
+                .active
+                .iter()
+                .skip(earliest)
+                .position(|job| job.node == *node && job.state == JobState::Waiting)
+            {
+                Some(offset) => {
+                    let job = &mut self.active[earliest + offset];
+                    job.kind = job.kind.merge(*kind).unwrap();
+                    job.transactions.push(transaction_id);
+
+                    earliest + offset
+                }
+                None => {
+                    self.next_id += 1;
+                    self.active.push_back(Job {
+                        id: self.next_id,
+                        node: node.clone(),
+                        kind: *kind,
+                        state: JobState::Waiting,
+                        transactions: vec![transaction_id],
+                    });
+
+                    self.active.len() - 1
+                }
+            };
+
+            ids.push(self.active[position].id);
+            earliest = position + 1;
+        }
+
+        if awaited {
+            for id in ids.iter() {
+                *self.awaited.entry(*id).or_default() += 1;
+            }
+        }
+
+        Ok(ids)
+    }
+
+    /// Lets finished jobs of an awaited transaction be forgotten.
+    pub(crate) fn release(&mut self, ids: &[JobId]) {
+        for id in ids.iter() {
+            if let Some(count) = self.awaited.get_mut(id) {
+                *count -= 1;
+                if *count == 0 {
+                    self.awaited.remove(id);
+                }
+            }
+        }
+
+        self.trim();
+    }
+
+    /// Forgets the oldest finished jobs beyond `FINISHED_JOBS_LENGTH` that are not awaited.
+    fn trim(&mut self) {
+        while self.finished.len() > FINISHED_JOBS_LENGTH {
+            let Some(position) = self
+                .finished
+                .iter()
+                .position(|job| !self.awaited.contains_key(&job.id))
+            else {
+                break;
+            };
+
+            self.finished.remove(position);
+        }
+    }
+
+    /// Marks the oldest waiting job as running and returns it.
+    pub(crate) fn next(&mut self) -> Option<Job> {
+        let job = self
+            .active
+            .iter_mut()
+            .find(|job| job.state == JobState::Waiting)?;
+        job.state = JobState::Running;
+
+        Some(job.clone())
+    }
+
+    /// Records the outcome of a job: a failed (or cancelled) job cancels the jobs
+    /// of its transactions that are still waiting.
+    pub(crate) fn finish(&mut self, id: JobId, state: JobState) {
+        let Some(position) = self.active.iter().position(|job| job.id == id) else {
+            return;
+        };
+
+        let mut job = self.active.remove(position).unwrap();
+        job.state = state;
+
+        let aborted = match job.state {
+            JobState::Failed(_) | JobState::Cancelled => job.transactions.clone(),
+            _ => vec![],
+        };
+
+        self.finished.push_back(job);
+        self.trim();
+
+        let cancelled = self
+            .active
+            .iter()
+            .filter(|other| other.state == JobState::Waiting)
+            .filter(|other| other.transactions.iter().any(|t| aborted.contains(t)))
+            .map(|other| other.id)
+            .collect::<Vec<_>>();
+        for id in cancelled.into_iter() {
+            self.finish(id, JobState::Cancelled);
+        }
+    }
+
+    /// Cancels a waiting job: returns false if the job is not waiting.
+    pub(crate) fn cancel(&mut self, id: JobId) -> bool {
+        match self.get(id) {
+            Some(job) if job.state == JobState::Waiting => {
+                self.finish(id, JobState::Cancelled);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns whether a job waiting to be run starts (or restarts) the given node.
+    pub(crate) fn start_pending(&self, node: &str) -> bool {
+        self.active.iter().any(|job| {
+            job.node == node
+                && job.state == JobState::Waiting
+                && matches!(job.kind, JobKind::Start | JobKind::Restart)
+        })
+    }
+
+    pub(crate) fn get(&self, id: JobId) -> Option<Job> {
+        self.active
+            .iter()
+            .chain(self.finished.iter())
+            .find(|job| job.id == id)
+            .cloned()
+    }
+
+    /// Returns pending jobs followed by the most recently finished ones.
+    pub(crate) fn list(&self) -> Vec<Job> {
+        self.active
+            .iter()
+            .chain(self.finished.iter().rev())
+            .cloned()
+            .collect()
+    }
+}
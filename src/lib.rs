@@ -20,10 +20,12 @@
 pub mod dbus;
 pub mod desc;
 pub mod errors;
+pub mod job;
 pub mod manager;
 pub mod node;
-pub mod signal;
+pub mod rusage;
 pub mod sessionexec;
+pub mod signal;
 
 pub use zbus;
 
@@ -24,17 +24,20 @@ use std::sync::Arc;
 use sessionrunner::dbus::SessionManagerDBus;
 use sessionrunner::desc::NodeServiceDescriptor;
 use sessionrunner::errors::SessionManagerError;
-use sessionrunner::manager::SessionManager;
-use sessionrunner::node::{SessionNode, SessionNodeRestart, SessionNodeType};
-use sessionrunner::signal::Signal;
-use std::time::{SystemTime, UNIX_EPOCH};
+use sessionrunner::manager::{RunOutcome, SessionEnd, SessionManager};
+use sessionrunner::node::{
+    SessionNode, SessionNodeDependencies, SessionNodeHooks, SessionNodeReload, SessionNodeRestart,
+    SessionNodeStop, SessionNodeType,
+};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::signal::unix::{signal, Signal, SignalKind};
 use zbus::connection;
 
 pub(crate) fn get_home_dir(uid: u32) -> Option<String> {
     unsafe {
         let mut result = std::ptr::null_mut();
         let amt = match libc::sysconf(libc::_SC_GETPW_R_SIZE_MAX) {
-            n if n < 0 => 512 as usize,
+            n if n < 0 => 512,
             n => n as usize,
         };
         let mut buf = Vec::with_capacity(amt);
@@ -61,7 +64,7 @@ pub(crate) fn get_shell(uid: u32) -> Option<String> {
     unsafe {
         let mut result = std::ptr::null_mut();
         let amt = match libc::sysconf(libc::_SC_GETPW_R_SIZE_MAX) {
-            n if n < 0 => 512 as usize,
+            n if n < 0 => 512,
             n => n as usize,
         };
         let mut buf = Vec::with_capacity(amt);
@@ -84,11 +87,84 @@ pub(crate) fn get_shell(uid: u32) -> Option<String> {
     }
 }
 
+/// The session ended cleanly: the main node has exited successfully or has been stopped.
+const EXIT_SUCCESS: i32 = 0;
+
+/// The main node has failed and no rescue target could take its place.
+const EXIT_MAIN_FAILED: i32 = 2;
+
+/// The main node has failed and the session ended in the rescue target.
+const EXIT_RESCUED: i32 = 3;
+
+/// Time given to the nodes to stop when sessionrunner is asked to terminate:
+/// the ones still running afterwards are killed.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Reacts to the signals sent to sessionrunner: SIGTERM and SIGINT shut the session down,
+/// SIGHUP reloads the configuration and SIGUSR1 logs the state of every node.
+async fn handle_signals(
+    manager: Arc<SessionManager>,
+    mut terminate: Signal,
+    mut interrupt: Signal,
+    mut hangup: Signal,
+    mut user_defined1: Signal,
+) {
+    loop {
+        tokio::select! {
+            _ = terminate.recv() => {
+                match manager.request_shutdown(SHUTDOWN_TIMEOUT) {
+                    true => eprintln!("Received SIGTERM: shutting down the session"),
+                    false => eprintln!("Received SIGTERM: already shutting down the session"),
+                }
+            }
+            _ = interrupt.recv() => {
+                match manager.request_shutdown(SHUTDOWN_TIMEOUT) {
+                    true => eprintln!("Received SIGINT: shutting down the session"),
+                    false => eprintln!("Received SIGINT: already shutting down the session"),
+                }
+            }
+            _ = hangup.recv() => {
+                eprintln!("Received SIGHUP: reloading the configuration");
+
+                match manager.daemon_reload().await {
+                    Ok(outcome) => {
+                        for name in outcome.needs_restart() {
+                            eprintln!("{name} has changed and needs to be restarted");
+                        }
+
+                        for error in outcome.skipped() {
+                            eprintln!("Error loading {error}");
+                        }
+                    }
+                    Err(err) => eprintln!("{err}"),
+                }
+            }
+            _ = user_defined1.recv() => {
+                eprintln!("State of the session:");
+
+                for line in manager.dump().await {
+                    eprintln!("  {line}");
+                }
+            }
+        }
+    }
+}
+
+fn exit_code(outcome: &RunOutcome) -> i32 {
+    match outcome.end() {
+        SessionEnd::Completed | SessionEnd::Stopped => EXIT_SUCCESS,
+        SessionEnd::Failed => EXIT_MAIN_FAILED,
+        SessionEnd::Rescued => EXIT_RESCUED,
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<(), SessionManagerError> {
     let user_homedir = PathBuf::from(
         get_home_dir(unsafe { libc::getuid() }).expect("Failed to get user information"),
     );
+    // sorted by decreasing priority: user configuration overrides
+    // the system one, that in turn overrides the vendor one.
     let load_directories = vec![
         user_homedir.join(".config").join("sessionrunner"),
         PathBuf::from("/etc/sessionrunner/"),
@@ -97,6 +173,14 @@ async fn main() -> Result<(), SessionManagerError> {
 
     let default_service_name = String::from("default.service");
 
+    // the session is switched to the rescue target when the default one fails:
+    // it can be changed (or disabled by setting it empty) from the environment
+    let rescue_service_name = match std::env::var("SESSIONRUNNER_RESCUE_TARGET") {
+        Ok(name) if name.is_empty() => None,
+        Ok(name) => Some(name),
+        Err(_) => Some(String::from("rescue.service")),
+    };
+
     let mut nodes = HashMap::new();
     match NodeServiceDescriptor::load_tree(
         &mut nodes,
@@ -129,10 +213,13 @@ async fn main() -> Result<(), SessionManagerError> {
                             None,
                             shell.clone(),
                             vec![],
-                            Signal::SIGTERM,
+                            SessionNodeStop::default(),
                             SessionNodeRestart::no_restart(),
-                            Vec::new(),
+                            SessionNodeDependencies::default(),
                             HashMap::new(),
+                            SessionNodeHooks::default(),
+                            SessionNodeReload::default(),
+                            None,
                         )),
                     )])
                 } else {
@@ -152,9 +239,41 @@ async fn main() -> Result<(), SessionManagerError> {
                 eprintln!("JSON syntax error: unrecognised kind value {err}");
                 std::process::exit(-1)
             }
+            sessionrunner::errors::NodeLoadingError::InvalidSignal(err) => {
+                eprintln!("JSON syntax error: unrecognised signal value {err}");
+                std::process::exit(-1)
+            }
+            sessionrunner::errors::NodeLoadingError::InvalidDropIn(filename) => {
+                eprintln!("Invalid drop-in override: {filename}");
+                std::process::exit(-1)
+            }
+            sessionrunner::errors::NodeLoadingError::InvalidExtends(filename) => {
+                eprintln!("Invalid extends in: {filename}");
+                std::process::exit(-1)
+            }
+            sessionrunner::errors::NodeLoadingError::CyclicExtends(filename) => {
+                eprintln!("Cycle in inheritance: {filename}");
+                std::process::exit(-1)
+            }
+            sessionrunner::errors::NodeLoadingError::InvalidCommand(filename) => {
+                eprintln!("Invalid command for the kind of: {filename}");
+                std::process::exit(-1)
+            }
+            sessionrunner::errors::NodeLoadingError::InvalidPolicy(err) => {
+                eprintln!("JSON syntax error: unrecognised on_dependency_failure value {err}");
+                std::process::exit(-1)
+            }
         },
     };
 
+    // every other service is loaded to be started on demand:
+    // a broken descriptor must not prevent the session from starting
+    for (filename, err) in
+        NodeServiceDescriptor::load_all(&mut nodes, load_directories.as_slice()).await
+    {
+        eprintln!("Error loading {filename}: {err}");
+    }
+
     // the XDG_RUNTIME_DIR is required for generating the default dbus socket path
     // and also the runtime directory (hopefully /tmp mounted) to keep track of services
     let xdg_runtime_dir = PathBuf::from(std::env::var("XDG_RUNTIME_DIR").unwrap());
@@ -169,7 +288,11 @@ async fn main() -> Result<(), SessionManagerError> {
 
     std::fs::create_dir(manager_runtime_path.clone()).unwrap();
 
-    let manager = Arc::new(SessionManager::new(nodes));
+    let manager = Arc::new(SessionManager::new(
+        nodes,
+        load_directories,
+        rescue_service_name,
+    ));
 
     let dbus_manager = connection::Builder::session()
         .map_err(SessionManagerError::ZbusError)?
@@ -184,11 +307,35 @@ async fn main() -> Result<(), SessionManagerError> {
         .await
         .map_err(SessionManagerError::ZbusError)?;
 
+    // handlers are installed before anything is started so that no signal is missed
+    tokio::spawn(handle_signals(
+        manager.clone(),
+        signal(SignalKind::terminate())?,
+        signal(SignalKind::interrupt())?,
+        signal(SignalKind::hangup())?,
+        signal(SignalKind::user_defined1())?,
+    ));
+
     println!("Running the session manager");
 
-    manager.run(&default_service_name).await?;
+    let outcome = manager.run(&default_service_name).await?;
+
+    // whatever ended the session the remaining nodes, each in its own process group,
+    // are stopped in order: a shutdown already in progress is waited for instead
+    manager.shutdown(SHUTDOWN_TIMEOUT).await;
 
     drop(dbus_manager);
 
-    Ok(())
+    for name in outcome.failed() {
+        eprintln!("{name} has failed");
+    }
+
+    let code = exit_code(&outcome);
+    println!(
+        "Session ended ({:?}, main node result: {:?}): exiting with code {code}",
+        outcome.end(),
+        outcome.main()
+    );
+
+    std::process::exit(code)
 }
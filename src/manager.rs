@@ -17,13 +17,34 @@
     51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
 */
 
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
-use tokio::task::{self, JoinSet};
+use tokio::{
+    sync::{
+        mpsc::{self, UnboundedReceiver, UnboundedSender},
+        oneshot, Mutex, Notify, RwLock,
+    },
+    time::sleep,
+};
 
 use crate::{
-    errors::SessionManagerError,
-    node::{ManualAction, SessionNode},
+    desc::NodeServiceDescriptor,
+    errors::{NodeLoadingError, SessionManagerError},
+    job::{Job, JobId, JobKind, JobQueue, JobState},
+    node::{
+        KillWhom, ManualAction, ManualActionIssueError, NodeFailure, RunResult, SessionNode,
+        SessionNodeRun, SessionNodeSource, SessionNodeStatus, SessionNodeStopReason,
+        SessionNodeType,
+    },
+    signal::Signal,
 };
 
 pub struct ManagerStatus {
@@ -36,38 +57,960 @@ impl ManagerStatus {
     }
 }
 
-#[derive(Debug, Default)]
+/// The outcome of a start request.
+#[derive(Debug, Clone, Default)]
+pub struct StartOutcome {
+    started: bool,
+    stopped: Vec<String>,
+}
+
+impl StartOutcome {
+    /// Returns whether the target has been started: false if it was already being supervised.
+    pub fn started(&self) -> bool {
+        self.started
+    }
+
+    /// Returns the conflicting nodes that have been stopped to start the target.
+    pub fn stopped(&self) -> &[String] {
+        self.stopped.as_slice()
+    }
+}
+
+/// A step of an isolate transaction, reported while the transaction is carried out.
+#[derive(Debug, Clone, PartialEq)]
+pub enum IsolateStep {
+    Stopping(String),
+    Stopped(String),
+    Starting(String),
+}
+
+impl IsolateStep {
+    pub fn action(&self) -> &str {
+        match self {
+            IsolateStep::Stopping(_) => "stopping",
+            IsolateStep::Stopped(_) => "stopped",
+            IsolateStep::Starting(_) => "starting",
+        }
+    }
+
+    pub fn node(&self) -> &str {
+        match self {
+            IsolateStep::Stopping(node)
+            | IsolateStep::Stopped(node)
+            | IsolateStep::Starting(node) => node.as_str(),
+        }
+    }
+}
+
+/// How a stop request treats the nodes depending on its target.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum StopMode {
+    /// Refuse to stop the target while nodes requiring it are running
+    Plain,
+    /// Stop only the target, leaving the nodes depending on it running
+    Force,
+    /// Stop every running node depending on the target before the target itself
+    Cascade,
+}
+
+impl TryFrom<&str> for StopMode {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "plain" => Ok(StopMode::Plain),
+            "force" => Ok(StopMode::Force),
+            "cascade" => Ok(StopMode::Cascade),
+            _ => Err(format!("Invalid stop mode: {value}")),
+        }
+    }
+}
+
+/// The outcome of a configuration reload.
+#[derive(Debug, Clone, Default)]
+pub struct ReloadOutcome {
+    added: Vec<String>,
+    removed: Vec<String>,
+    changed: Vec<String>,
+    needs_restart: Vec<String>,
+    skipped: Vec<String>,
+}
+
+impl ReloadOutcome {
+    /// Returns the nodes that have been loaded for the first time.
+    pub fn added(&self) -> &[String] {
+        self.added.as_slice()
+    }
+
+    /// Returns the nodes whose descriptor is gone: those have been stopped.
+    pub fn removed(&self) -> &[String] {
+        self.removed.as_slice()
+    }
+
+    /// Returns the changed nodes that have taken their new definition.
+    pub fn changed(&self) -> &[String] {
+        self.changed.as_slice()
+    }
+
+    /// Returns the changed nodes that keep their old definition until those
+    /// are restarted (or started again once stopped).
+    pub fn needs_restart(&self) -> &[String] {
+        self.needs_restart.as_slice()
+    }
+
+    /// Returns the descriptors no loaded node references that have failed to load,
+    /// together with the error: those have been skipped.
+    pub fn skipped(&self) -> &[String] {
+        self.skipped.as_slice()
+    }
+}
+
+/// The outcome of an isolate request.
+#[derive(Debug, Clone, Default)]
+pub struct IsolateOutcome {
+    started: Vec<String>,
+    stopped: Vec<String>,
+}
+
+impl IsolateOutcome {
+    /// Returns the nodes of the target closure that were not already supervised.
+    pub fn started(&self) -> &[String] {
+        self.started.as_slice()
+    }
+
+    /// Returns the nodes outside of the target closure that have been stopped.
+    pub fn stopped(&self) -> &[String] {
+        self.stopped.as_slice()
+    }
+}
+
+/// How a session has ended.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum SessionEnd {
+    /// The main node has exited successfully
+    Completed,
+    /// The main node has been stopped on request
+    Stopped,
+    /// The main node has failed, and no rescue target could take its place
+    Failed,
+    /// The main node has failed and the rescue target, that took its place, has exited
+    Rescued,
+}
+
+/// The outcome of running a session.
+#[derive(Debug)]
+pub struct RunOutcome {
+    main: RunResult,
+    failed: Vec<String>,
+    end: SessionEnd,
+}
+
+impl RunOutcome {
+    /// Returns the result of the last run of the main node.
+    pub fn main(&self) -> &RunResult {
+        &self.main
+    }
+
+    /// Returns the nodes that had failed for good when the session ended.
+    pub fn failed(&self) -> &[String] {
+        self.failed.as_slice()
+    }
+
+    pub fn end(&self) -> SessionEnd {
+        self.end
+    }
+}
+
+/// Why the session has been switched to the rescue target.
+#[derive(Debug, Clone)]
+pub struct RescueDecision {
+    target: String,
+    cause: String,
+}
+
+impl RescueDecision {
+    pub fn target(&self) -> &str {
+        self.target.as_str()
+    }
+
+    pub fn cause(&self) -> &str {
+        self.cause.as_str()
+    }
+}
+
+#[derive(Debug)]
 pub struct SessionManager {
-    services: HashMap<String, Arc<SessionNode>>,
+    services: RwLock<HashMap<String, Arc<SessionNode>>>,
+    directories: Vec<PathBuf>,
+    rescue: Option<String>,
+    rescued: RwLock<Option<RescueDecision>>,
+    needs_restart: RwLock<Vec<String>>,
+    transient: RwLock<Vec<String>>,
+    shutting_down: AtomicBool,
+    shut_down: AtomicBool,
+    shutdown_notify: Notify,
+    main: RwLock<Option<String>>,
+    jobs: std::sync::Mutex<JobQueue>,
+    jobs_notify: Notify,
+    dispatcher: Mutex<()>,
+    failures: UnboundedSender<NodeFailure>,
+    failures_receiver: Mutex<UnboundedReceiver<NodeFailure>>,
 }
 
 impl SessionManager {
-    pub fn new(map: HashMap<String, Arc<SessionNode>>) -> Self {
-        let services = map
-            .into_iter()
-            .map(|(name, node)| (name.clone(), node.clone()))
-            .collect::<HashMap<String, Arc<SessionNode>>>();
+    /// Creates a manager for the given services: `directories` are the ones services
+    /// have been loaded from, and are used to load template instances on demand.
+    ///
+    /// The session is switched to the `rescue` target (if any) when the main node fails.
+    pub fn new(
+        map: HashMap<String, Arc<SessionNode>>,
+        directories: Vec<PathBuf>,
+        rescue: Option<String>,
+    ) -> Self {
+        let (failures, failures_receiver) = mpsc::unbounded_channel();
+        Self::adopt(&failures, &map);
 
-        Self { services }
+        let services = RwLock::new(map);
+
+        Self {
+            services,
+            directories,
+            rescue,
+            rescued: RwLock::new(None),
+            needs_restart: RwLock::new(Vec::new()),
+            transient: RwLock::new(Vec::new()),
+            shutting_down: AtomicBool::new(false),
+            shut_down: AtomicBool::new(false),
+            shutdown_notify: Notify::new(),
+            main: RwLock::new(None),
+            jobs: std::sync::Mutex::new(JobQueue::default()),
+            jobs_notify: Notify::new(),
+            dispatcher: Mutex::new(()),
+            failures,
+            failures_receiver: Mutex::new(failures_receiver),
+        }
     }
 
-    pub async fn is_running(&self, target: &String) -> Result<bool, SessionManagerError> {
-        match self.services.get(target) {
-            Some(node) => Ok(node.is_running().await),
+    /// Has failures of the given nodes reported to the manager, that starts their handlers.
+    fn adopt(
+        failures: &UnboundedSender<NodeFailure>,
+        services: &HashMap<String, Arc<SessionNode>>,
+    ) {
+        for node in services.values() {
+            node.report_failures_to(failures.clone());
+        }
+    }
+
+    async fn node(&self, target: &String) -> Result<Arc<SessionNode>, SessionManagerError> {
+        match self.services.read().await.get(target) {
+            Some(node) => Ok(node.clone()),
             None => Err(SessionManagerError::NotFound(target.clone())),
         }
     }
 
-    pub async fn start(&self, target: &String) -> Result<bool, SessionManagerError> {
-        todo!()
+    /// Returns the requested node, loading it (and its dependencies) from the
+    /// search path if it is not already known: this is how template instances
+    /// are created.
+    async fn node_or_load(&self, target: &String) -> Result<Arc<SessionNode>, SessionManagerError> {
+        let mut services = self.services.write().await;
+        if let Some(node) = services.get(target) {
+            return Ok(node.clone());
+        }
+
+        // load into a copy so that a failure doesn't leave a partially loaded tree
+        let mut loaded = services.clone();
+        match NodeServiceDescriptor::load_tree(&mut loaded, target, self.directories.as_slice())
+            .await
+        {
+            Ok(_) => {}
+            Err(NodeLoadingError::FileNotFound(filename)) if filename == *target => {
+                return Err(SessionManagerError::NotFound(target.clone()))
+            }
+            Err(err) => return Err(SessionManagerError::LoadingError(err)),
+        };
+
+        Self::adopt(&self.failures, &loaded);
+        *services = loaded;
+
+        match services.get(target) {
+            Some(node) => Ok(node.clone()),
+            None => Err(SessionManagerError::NotFound(target.clone())),
+        }
+    }
+
+    pub async fn kind(&self, target: &String) -> Result<SessionNodeType, SessionManagerError> {
+        Ok(self.node(target).await?.kind())
+    }
+
+    pub async fn is_running(&self, target: &String) -> Result<bool, SessionManagerError> {
+        Ok(self.node(target).await?.is_running().await)
+    }
+
+    pub async fn status(&self, target: &String) -> Result<SessionNodeStatus, SessionManagerError> {
+        Ok(self.node(target).await?.status().await)
+    }
+
+    pub async fn history(
+        &self,
+        target: &String,
+    ) -> Result<Vec<SessionNodeRun>, SessionManagerError> {
+        Ok(self.node(target).await?.history().await)
+    }
+
+    pub async fn source(
+        &self,
+        target: &String,
+    ) -> Result<Option<SessionNodeSource>, SessionManagerError> {
+        Ok(self.node(target).await?.source().cloned())
+    }
+
+    /// Starts the target (and its dependencies) if it is not already being supervised:
+    /// nodes conflicting with any of them are stopped, and have fully exited, beforehand.
+    pub async fn start(&self, target: &String) -> Result<StartOutcome, SessionManagerError> {
+        let jobs = self.run_jobs(target, JobKind::Start).await?;
+
+        Ok(StartOutcome {
+            started: jobs
+                .iter()
+                .any(|job| job.node() == target && *job.state() == JobState::Done),
+            stopped: jobs
+                .iter()
+                .filter(|job| job.kind() == JobKind::Stop && *job.state() == JobState::Done)
+                .map(|job| job.node().to_owned())
+                .collect(),
+        })
+    }
+
+    /// Queues the transaction a request on `target` expands to, returning the identifiers
+    /// of its jobs in the order those will run: starting a node also starts the nodes
+    /// it pulls in, after stopping every node conflicting with any of them.
+    ///
+    /// The whole transaction is refused if any of its jobs conflicts with a pending one,
+    /// and stops or restarts are refused while running nodes require their target.
+    pub async fn enqueue(
+        &self,
+        target: &String,
+        kind: JobKind,
+    ) -> Result<Vec<JobId>, SessionManagerError> {
+        let transaction = self.transaction(target, kind).await?;
+
+        self.queue(transaction.as_slice(), false).await
+    }
+
+    /// Returns the jobs a request on `target` expands to, in the order those have to run.
+    async fn transaction(
+        &self,
+        target: &String,
+        kind: JobKind,
+    ) -> Result<Vec<(String, JobKind)>, SessionManagerError> {
+        // restarting a node that is not supervised starts it, stopping conflicting nodes
+        let kind = match kind {
+            JobKind::Restart if !self.node(target).await?.is_supervised() => JobKind::Start,
+            kind => kind,
+        };
+
+        let transaction = match kind {
+            JobKind::Start => {
+                let closure = Self::closure(&self.node_or_load(target).await?);
+
+                self.conflicting(&closure)
+                    .await
+                    .iter()
+                    .map(|node| (node.name().to_owned(), JobKind::Stop))
+                    .chain(
+                        closure
+                            .iter()
+                            .map(|node| (node.name().to_owned(), JobKind::Start)),
+                    )
+                    .collect::<Vec<_>>()
+            }
+            // queued jobs are held to the checks of a plain stop
+            JobKind::Stop => Self::stop_transaction(&self.node(target).await?, StopMode::Plain)?,
+            JobKind::Restart => {
+                Self::refuse_if_required(&self.node(target).await?, true)?;
+
+                vec![(target.clone(), kind)]
+            }
+        };
+
+        Ok(transaction)
+    }
+
+    /// Queues a transaction: an awaited one has to be passed to `run_transaction`.
+    async fn queue(
+        &self,
+        transaction: &[(String, JobKind)],
+        awaited: bool,
+    ) -> Result<Vec<JobId>, SessionManagerError> {
+        let ids = self
+            .jobs
+            .lock()
+            .unwrap()
+            .add_transaction(transaction, awaited)
+            .map_err(SessionManagerError::JobConflict)?;
+
+        self.flag_pending_starts().await;
+
+        Ok(ids)
+    }
+
+    /// Lets every node know whether a queued job is about to start it, so that
+    /// nodes ordered after it wait for it instead of considering it satisfied.
+    async fn flag_pending_starts(&self) {
+        let services = self.services.read().await;
+        let jobs = self.jobs.lock().unwrap();
+        for node in services.values() {
+            node.set_start_pending(jobs.start_pending(node.name()));
+        }
+    }
+
+    /// Runs queued jobs one after the other until none is left waiting:
+    /// whoever is already dispatching also runs jobs queued in the meantime.
+    pub async fn dispatch(&self) {
+        let _dispatching = self.dispatcher.lock().await;
+
+        loop {
+            let Some(job) = self.jobs.lock().unwrap().next() else {
+                break;
+            };
+
+            let state = self.execute(&job).await;
+
+            self.jobs.lock().unwrap().finish(job.id(), state);
+            self.flag_pending_starts().await;
+            self.jobs_notify.notify_waiters();
+        }
     }
 
-    pub async fn stop(&self, target: &String) -> Result<(), SessionManagerError> {
-        self.manual_action(target, ManualAction::Stop).await
+    async fn execute(&self, job: &Job) -> JobState {
+        let node = match self.node(&job.node().to_owned()).await {
+            Ok(node) => node,
+            Err(err) => return JobState::Failed(err.to_string()),
+        };
+
+        match job.kind() {
+            JobKind::Start if !node.is_supervised() && self.is_stale(node.name()).await => {
+                match self.replace_stale(&node).await {
+                    Ok(fresh) => match SessionNode::start(fresh).await {
+                        true => JobState::Done,
+                        false => JobState::Redundant,
+                    },
+                    Err(err) => JobState::Failed(err.to_string()),
+                }
+            }
+            JobKind::Start => match SessionNode::start(node).await {
+                true => JobState::Done,
+                false => JobState::Redundant,
+            },
+            JobKind::Stop if !node.is_supervised() => JobState::Redundant,
+            JobKind::Stop => {
+                match SessionNode::issue_manual_action(node.clone(), ManualAction::Stop).await {
+                    Ok(_) => {
+                        node.wait_for_supervision_end().await;
+
+                        JobState::Done
+                    }
+                    Err(err) => JobState::Failed(err.to_string()),
+                }
+            }
+            // stopped after the restart has been queued: starting it would skip conflicts
+            JobKind::Restart if !node.is_supervised() => {
+                JobState::Failed(format!("{} is not running anymore", node.name()))
+            }
+            // the main node is restarted in place: stopping it would end the session
+            JobKind::Restart
+                if self.is_stale(node.name()).await
+                    && self.main.read().await.as_deref() != Some(node.name()) =>
+            {
+                match self.restart_stale(node).await {
+                    Ok(_) => JobState::Done,
+                    Err(err) => JobState::Failed(err.to_string()),
+                }
+            }
+            JobKind::Restart => {
+                match SessionNode::issue_manual_action(node, ManualAction::Restart).await {
+                    Ok(_) => JobState::Done,
+                    Err(err) => JobState::Failed(err.to_string()),
+                }
+            }
+        }
+    }
+
+    /// Returns whether the node has been kept with a definition that has changed since.
+    async fn is_stale(&self, name: &str) -> bool {
+        self.needs_restart
+            .read()
+            .await
+            .iter()
+            .any(|other| other == name)
+    }
+
+    /// Restarts a running node kept with a stale definition so that it comes back with
+    /// the new one: the nodes bound to (or part of) it are stopped together with it and
+    /// started again afterwards, as they would be when restarting it in place.
+    async fn restart_stale(&self, node: Arc<SessionNode>) -> Result<(), SessionManagerError> {
+        let bound = node
+            .dependents()
+            .into_iter()
+            .filter(|dependent| dependent.is_supervised())
+            .filter(|dependent| {
+                dependent
+                    .dependencies()
+                    .binds_to()
+                    .iter()
+                    .any(|dependency| dependency.name() == node.name())
+                    || dependent
+                        .dependencies()
+                        .part_of()
+                        .iter()
+                        .any(|name| name == node.name())
+            })
+            .collect::<Vec<_>>();
+
+        SessionNode::issue_manual_action(node.clone(), ManualAction::Stop).await?;
+        node.wait_for_supervision_end().await;
+        for dependent in bound.iter() {
+            dependent.wait_for_supervision_end().await;
+        }
+
+        SessionNode::start(self.replace_stale(&node).await?).await;
+        for dependent in bound.iter() {
+            SessionNode::start(self.node(&dependent.name().to_owned()).await?).await;
+        }
+
+        Ok(())
+    }
+
+    /// Replaces a stopped node kept with a stale definition by the one loaded from disk,
+    /// returning the latter: stopped nodes referencing it are loaded again too, while
+    /// running ones keep referencing the stale node until they are restarted themselves.
+    async fn replace_stale(
+        &self,
+        node: &Arc<SessionNode>,
+    ) -> Result<Arc<SessionNode>, SessionManagerError> {
+        let transient = self.transient.read().await.clone();
+        let directories = self.directories.as_slice();
+
+        let (replaced, referencing): (Vec<_>, Vec<_>) = Self::reverse_closure(node)
+            .into_iter()
+            .filter(|other| !transient.iter().any(|name| name == other.name()))
+            .partition(|other| other.name() == node.name() || !other.is_supervised());
+
+        let mut services = self.services.write().await;
+        let mut loaded = services.clone();
+        for other in replaced.iter() {
+            loaded.remove(other.name());
+        }
+
+        for other in replaced.iter() {
+            let name = other.name().to_owned();
+            if let Err(err) =
+                NodeServiceDescriptor::load_tree(&mut loaded, &name, directories).await
+            {
+                return Err(SessionManagerError::ReloadFailed(format!("{name}: {err}")));
+            }
+
+            loaded.get(&name).unwrap().inherit_runs(other).await;
+        }
+
+        let fresh = loaded.get(node.name()).unwrap().clone();
+
+        Self::adopt(&self.failures, &loaded);
+        *services = loaded;
+        drop(services);
+
+        let mut needs_restart = self.needs_restart.write().await;
+        needs_restart.retain(|name| name != node.name());
+        for other in referencing.iter() {
+            if !needs_restart.iter().any(|name| name == other.name()) {
+                needs_restart.push(other.name().to_owned());
+            }
+        }
+
+        Ok(fresh)
+    }
+
+    /// Queues a request, runs it and waits for every job of its transaction to finish.
+    async fn run_jobs(
+        &self,
+        target: &String,
+        kind: JobKind,
+    ) -> Result<Vec<Job>, SessionManagerError> {
+        let transaction = self.transaction(target, kind).await?;
+        let ids = self.queue(transaction.as_slice(), true).await?;
+
+        self.run_transaction(ids).await
+    }
+
+    /// Runs queued jobs and waits for the given ones, of an awaited transaction, to finish:
+    /// the first failed job is reported, as the rest of the transaction has been cancelled.
+    async fn run_transaction(&self, ids: Vec<JobId>) -> Result<Vec<Job>, SessionManagerError> {
+        self.dispatch().await;
+
+        let mut jobs = vec![];
+        for id in ids.iter() {
+            // awaited jobs are kept until released
+            if let Some(job) = self.wait_job(*id).await {
+                jobs.push(job);
+            }
+        }
+
+        self.transaction_outcome(ids, jobs)
+    }
+
+    /// Releases the jobs of an awaited transaction, reporting the first failed one.
+    fn transaction_outcome(
+        &self,
+        ids: Vec<JobId>,
+        jobs: Vec<Job>,
+    ) -> Result<Vec<Job>, SessionManagerError> {
+        self.jobs.lock().unwrap().release(ids.as_slice());
+
+        let failure = jobs.iter().find_map(|job| match job.state() {
+            JobState::Failed(err) => Some(format!("{} of {}: {err}", job.kind(), job.node())),
+            _ => None,
+        });
+
+        match failure {
+            Some(failure) => Err(SessionManagerError::JobFailed(failure)),
+            None => Ok(jobs),
+        }
+    }
+
+    /// Waits for a job to finish and returns it: None if the job is not known.
+    pub async fn wait_job(&self, id: JobId) -> Option<Job> {
+        loop {
+            let job = self.jobs.lock().unwrap().get(id)?;
+            if job.state().is_finished() {
+                return Some(job);
+            }
+
+            tokio::select! {
+                _ = sleep(Duration::from_millis(250)) => {},
+                _ = self.jobs_notify.notified() => {},
+            };
+        }
+    }
+
+    pub fn job(&self, id: JobId) -> Result<Job, SessionManagerError> {
+        match self.jobs.lock().unwrap().get(id) {
+            Some(job) => Ok(job),
+            None => Err(SessionManagerError::NotFound(format!("job {id}"))),
+        }
+    }
+
+    /// Returns pending jobs followed by the most recently finished ones.
+    pub fn jobs(&self) -> Vec<Job> {
+        self.jobs.lock().unwrap().list()
+    }
+
+    /// Cancels a job that is still waiting to be run.
+    pub async fn cancel_job(&self, id: JobId) -> Result<(), SessionManagerError> {
+        {
+            let mut jobs = self.jobs.lock().unwrap();
+            if jobs.get(id).is_none() {
+                return Err(SessionManagerError::NotFound(format!("job {id}")));
+            }
+
+            if !jobs.cancel(id) {
+                return Err(SessionManagerError::JobNotCancellable(id));
+            }
+        }
+
+        self.flag_pending_starts().await;
+        self.jobs_notify.notify_waiters();
+
+        Ok(())
+    }
+
+    /// Returns every supervised node conflicting with any node in `closure`.
+    async fn conflicting(&self, closure: &[Arc<SessionNode>]) -> Vec<Arc<SessionNode>> {
+        let mut conflicting = self
+            .services
+            .read()
+            .await
+            .values()
+            .filter(|other| !closure.iter().any(|node| node.name() == other.name()))
+            .filter(|other| other.is_supervised())
+            .filter(|other| closure.iter().any(|node| node.conflicts_with(other)))
+            .cloned()
+            .collect::<Vec<_>>();
+        conflicting.sort_by(|a, b| a.name().cmp(b.name()));
+
+        conflicting
+    }
+
+    /// Switches the session to `target` in a single transaction: every other supervised
+    /// node is stopped, dependents first, then its closure is started. The main node is
+    /// never stopped as that would end the session: conflicting with it is an error.
+    ///
+    /// The target and its closure are resolved before anything is changed, so that
+    /// a target that cannot be loaded leaves the session untouched, and the transaction
+    /// is aborted as soon as a node fails to stop. Each step is sent to `progress`
+    /// (if given) while it is carried out.
+    pub async fn isolate(
+        &self,
+        target: &String,
+        progress: Option<UnboundedSender<IsolateStep>>,
+    ) -> Result<IsolateOutcome, SessionManagerError> {
+        let node = self.node_or_load(target).await?;
+        let closure = Self::closure(&node);
+
+        let main = self.main.read().await.clone();
+        if let Some(main) = self
+            .conflicting(&closure)
+            .await
+            .iter()
+            .find(|other| main.as_deref() == Some(other.name()))
+        {
+            return Err(SessionManagerError::ConflictsWithMain(
+                target.clone(),
+                main.name().to_owned(),
+            ));
+        }
+
+        let mut others = self
+            .services
+            .read()
+            .await
+            .values()
+            .filter(|other| !closure.iter().any(|node| node.name() == other.name()))
+            .filter(|other| other.is_supervised())
+            .filter(|other| main.as_deref() != Some(other.name()))
+            .cloned()
+            .collect::<Vec<_>>();
+        others.sort_by(|a, b| a.name().cmp(b.name()));
+
+        // nodes depending on another node to stop are stopped before it
+        let mut to_stop: Vec<Arc<SessionNode>> = vec![];
+        for other in others.iter() {
+            for dependent in Self::reverse_closure(other) {
+                if others.iter().any(|node| node.name() == dependent.name())
+                    && !to_stop.iter().any(|node| node.name() == dependent.name())
+                {
+                    to_stop.push(dependent);
+                }
+            }
+        }
+
+        let transaction = to_stop
+            .iter()
+            .map(|other| (other.name().to_owned(), JobKind::Stop))
+            .chain(
+                closure
+                    .iter()
+                    .map(|node| (node.name().to_owned(), JobKind::Start)),
+            )
+            .collect::<Vec<_>>();
+        let stopped_before = closure
+            .iter()
+            .filter(|node| !node.is_supervised())
+            .map(|node| node.name().to_owned())
+            .collect::<Vec<_>>();
+
+        let report = |step: IsolateStep| {
+            if let Some(progress) = &progress {
+                let _ = progress.send(step);
+            }
+        };
+
+        let ids = self.queue(transaction.as_slice(), true).await?;
+        let (_, jobs) = tokio::join!(self.dispatch(), async {
+            let mut jobs = vec![];
+            for (id, (name, kind)) in ids.iter().zip(transaction.iter()) {
+                match kind {
+                    JobKind::Stop => report(IsolateStep::Stopping(name.clone())),
+                    _ if stopped_before.contains(name) => {
+                        report(IsolateStep::Starting(name.clone()))
+                    }
+                    _ => {}
+                }
+
+                // awaited jobs are kept until released
+                let Some(job) = self.wait_job(*id).await else {
+                    continue;
+                };
+
+                match job.state() {
+                    JobState::Done if job.kind() == JobKind::Stop => {
+                        report(IsolateStep::Stopped(name.clone()))
+                    }
+                    // the rest of the transaction has been cancelled
+                    JobState::Failed(_) => {
+                        jobs.push(job);
+                        break;
+                    }
+                    _ => {}
+                }
+
+                jobs.push(job);
+            }
+
+            jobs
+        });
+
+        let jobs = self.transaction_outcome(ids, jobs)?;
+
+        let done = |kind: JobKind| {
+            let mut nodes = jobs
+                .iter()
+                .filter(|job| job.kind() == kind && *job.state() == JobState::Done)
+                .map(|job| job.node().to_owned())
+                .collect::<Vec<_>>();
+            nodes.sort();
+
+            nodes
+        };
+
+        Ok(IsolateOutcome {
+            started: done(JobKind::Start),
+            stopped: done(JobKind::Stop),
+        })
+    }
+
+    /// Returns `node` and every node it (transitively) pulls in, dependencies first.
+    fn closure(node: &Arc<SessionNode>) -> Vec<Arc<SessionNode>> {
+        let mut closure = vec![];
+        Self::collect_pulled_in(node, &mut closure);
+
+        closure
+    }
+
+    fn collect_pulled_in(node: &Arc<SessionNode>, collected: &mut Vec<Arc<SessionNode>>) {
+        if collected.iter().any(|other| other.name() == node.name()) {
+            return;
+        }
+
+        for dependency in node.dependencies().pulled_in() {
+            Self::collect_pulled_in(dependency, collected);
+        }
+
+        collected.push(node.clone());
+    }
+
+    /// Returns `node` and every node (transitively) depending on it, dependents first.
+    fn reverse_closure(node: &Arc<SessionNode>) -> Vec<Arc<SessionNode>> {
+        let mut visited = vec![];
+        let mut closure = vec![];
+        Self::collect_dependents(node, &mut visited, &mut closure);
+
+        closure
+    }
+
+    fn collect_dependents(
+        node: &Arc<SessionNode>,
+        visited: &mut Vec<String>,
+        collected: &mut Vec<Arc<SessionNode>>,
+    ) {
+        // relations such as part_of usually go both ways: never visit a node twice
+        if visited.iter().any(|name| name == node.name()) {
+            return;
+        }
+        visited.push(node.name().to_owned());
+
+        for dependent in node.dependents() {
+            Self::collect_dependents(&dependent, visited, collected);
+        }
+
+        collected.push(node.clone());
+    }
+
+    /// Returns the jobs stopping `node` as requested by `mode`, dependents first.
+    fn stop_transaction(
+        node: &Arc<SessionNode>,
+        mode: StopMode,
+    ) -> Result<Vec<(String, JobKind)>, SessionManagerError> {
+        let transaction = match mode {
+            StopMode::Plain => {
+                Self::refuse_if_required(node, false)?;
+
+                vec![(node.name().to_owned(), JobKind::Stop)]
+            }
+            StopMode::Force => vec![(node.name().to_owned(), JobKind::Stop)],
+            StopMode::Cascade => Self::reverse_closure(node)
+                .iter()
+                .filter(|other| other.name() == node.name() || other.is_supervised())
+                .map(|other| (other.name().to_owned(), JobKind::Stop))
+                .collect(),
+        };
+
+        Ok(transaction)
+    }
+
+    /// Refuses to take `node` down while nodes requiring (or bound to) it are running:
+    /// when restarting it nodes bound to it are not considered, as those are restarted too.
+    fn refuse_if_required(
+        node: &Arc<SessionNode>,
+        restarting: bool,
+    ) -> Result<(), SessionManagerError> {
+        let mut required_by = node
+            .dependents()
+            .iter()
+            .filter(|dependent| dependent.is_supervised())
+            .filter(|dependent| {
+                let bound = match restarting {
+                    true => &[],
+                    false => dependent.dependencies().binds_to(),
+                };
+
+                dependent
+                    .dependencies()
+                    .requires()
+                    .iter()
+                    .chain(bound.iter())
+                    .any(|dependency| dependency.name() == node.name())
+            })
+            .map(|dependent| dependent.name().to_owned())
+            .collect::<Vec<_>>();
+
+        if !required_by.is_empty() {
+            required_by.sort();
+            return Err(SessionManagerError::RequiredBy(
+                node.name().to_owned(),
+                required_by.join(", "),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Stops the target waiting for it to fully exit, returning every node that has been stopped.
+    ///
+    /// Unless the stop is forced or cascades to the nodes depending on the target,
+    /// it is refused while nodes requiring (or bound to) the target are running.
+    pub async fn stop(
+        &self,
+        target: &String,
+        mode: StopMode,
+    ) -> Result<Vec<String>, SessionManagerError> {
+        let transaction = Self::stop_transaction(&self.node(target).await?, mode)?;
+
+        let ids = self.queue(transaction.as_slice(), true).await?;
+
+        Ok(self
+            .run_transaction(ids)
+            .await?
+            .iter()
+            .filter(|job| *job.state() == JobState::Done)
+            .map(|job| job.node().to_owned())
+            .collect())
     }
 
     pub async fn restart(&self, target: &String) -> Result<(), SessionManagerError> {
-        self.manual_action(target, ManualAction::Restart).await
+        self.run_jobs(target, JobKind::Restart).await.map(|_| ())
+    }
+
+    pub async fn reload(&self, target: &String) -> Result<(), SessionManagerError> {
+        self.manual_action(target, ManualAction::Reload).await
+    }
+
+    pub async fn kill(
+        &self,
+        target: &String,
+        signal: Signal,
+        whom: KillWhom,
+    ) -> Result<(), SessionManagerError> {
+        Ok(self.node(target).await?.kill(signal, whom).await?)
     }
 
     async fn manual_action(
@@ -75,17 +1018,7 @@ impl SessionManager {
         target: &String,
         action: ManualAction,
     ) -> Result<(), SessionManagerError> {
-        let selected_node =
-            self.services
-                .iter()
-                .find_map(|(node_name, node_value)| match *target == **node_name {
-                    false => None,
-                    true => Some(node_value.clone()),
-                });
-
-        let Some(selected_node) = selected_node else {
-            return Err(SessionManagerError::NotFound(target.clone()));
-        };
+        let selected_node = self.node(target).await?;
 
         match SessionNode::issue_manual_action(selected_node, action).await {
             Ok(_) => Ok(()),
@@ -93,37 +1026,577 @@ impl SessionManager {
         }
     }
 
-    pub async fn run(&self, target: &String) -> Result<(), SessionManagerError> {
-        let mut other_nodes = vec![];
-        let mut main_node = None;
+    /// Returns whether the target is running with a definition that has changed since.
+    pub async fn needs_restart(&self, target: &String) -> Result<bool, SessionManagerError> {
+        self.node(target).await?;
+
+        Ok(self.needs_restart.read().await.contains(target))
+    }
+
+    /// Returns whether the target has been created at runtime rather than loaded from a file.
+    pub async fn is_transient(&self, target: &String) -> Result<bool, SessionManagerError> {
+        self.node(target).await?;
+
+        Ok(self.transient.read().await.contains(target))
+    }
+
+    /// Creates a node named `name` from the given JSON descriptor and starts it,
+    /// as `start` does: the node is forgotten as soon as it stops being supervised
+    /// unless `keep` is set, in which case it can be started again later on.
+    pub async fn start_transient(
+        manager: Arc<Self>,
+        name: &String,
+        descriptor: &str,
+        keep: bool,
+    ) -> Result<StartOutcome, SessionManagerError> {
+        if !name.ends_with(".service") || name.contains('@') {
+            return Err(SessionManagerError::InvalidArgument(format!(
+                "{name} is not a valid name for a transient node"
+            )));
+        }
+
+        {
+            let mut services = manager.services.write().await;
+            if services.contains_key(name) {
+                return Err(SessionManagerError::InvalidArgument(format!(
+                    "{name} already exists"
+                )));
+            }
+
+            // load into a copy so that a failure doesn't leave a partially loaded tree
+            let mut loaded = services.clone();
+            NodeServiceDescriptor::load_transient(
+                &mut loaded,
+                name,
+                descriptor,
+                manager.directories.as_slice(),
+            )
+            .await?;
+
+            Self::adopt(&manager.failures, &loaded);
+            *services = loaded;
+            manager.transient.write().await.push(name.clone());
+        }
+
+        let outcome = match manager.start(name).await {
+            Ok(outcome) => outcome,
+            Err(err) => {
+                manager.forget_transient(name).await;
+                return Err(err);
+            }
+        };
+
+        if !keep {
+            let node = manager.node(name).await?;
+            tokio::spawn(async move {
+                node.wait_for_supervision_end().await;
+
+                manager.forget_transient(node.name()).await
+            });
+        }
+
+        Ok(outcome)
+    }
+
+    /// Removes a transient node that is no longer supervised.
+    async fn forget_transient(&self, name: &str) {
+        let mut services = self.services.write().await;
+        if services.get(name).is_some_and(|node| node.is_supervised()) {
+            return;
+        }
+
+        services.remove(name);
+        self.transient.write().await.retain(|other| other != name);
+    }
+
+    /// Loads the configuration again and applies the differences with the running one:
+    /// new nodes are added, nodes whose descriptor is gone are stopped and removed,
+    /// and changed nodes take their new definition unless they (or any node depending
+    /// on them) are running, in which case they are marked as needing a restart:
+    /// the new definition is then taken as soon as they are restarted or started again.
+    ///
+    /// Nothing is changed if any descriptor referenced by a loaded node fails to load,
+    /// while the other descriptors failing to load are skipped and reported.
+    pub async fn daemon_reload(&self) -> Result<ReloadOutcome, SessionManagerError> {
+        // jobs are not run while nodes are being replaced
+        let _dispatching = self.dispatcher.lock().await;
+        let mut services = self.services.write().await;
+
+        let directories = self.directories.as_slice();
 
-        for (node_name, node_value) in self.services.iter() {
-            if *target == *node_name {
-                main_node = Some(node_value.clone())
-            } else {
-                other_nodes.push(node_value.clone());
+        // transient nodes have no descriptor to be loaded again: those are kept as they are
+        let transient = self.transient.read().await.clone();
+
+        // nodes loaded on demand (such as template instances) are loaded again by name
+        let mut fresh = HashMap::new();
+        let mut names = services
+            .keys()
+            .filter(|name| !transient.contains(name))
+            .cloned()
+            .collect::<Vec<_>>();
+        names.sort();
+        for name in names.iter() {
+            match NodeServiceDescriptor::load_tree(&mut fresh, name, directories).await {
+                Ok(_) => {}
+                Err(NodeLoadingError::FileNotFound(filename)) if filename == *name => {}
+                Err(err) => {
+                    return Err(SessionManagerError::ReloadFailed(format!("{name}: {err}")))
+                }
             }
         }
 
-        let Some(main_node) = main_node else {
-            return Err(SessionManagerError::NotFound(target.clone()));
+        // descriptors loaded nodes reference have been loaded above:
+        // the ones failing here are not referenced, and are skipped
+        let skipped = NodeServiceDescriptor::load_all(&mut fresh, directories)
+            .await
+            .into_iter()
+            .map(|(filename, err)| format!("{filename}: {err}"))
+            .collect::<Vec<_>>();
+
+        let unchanged = |node: &Arc<SessionNode>| match fresh.get(node.name()) {
+            Some(other) => {
+                node.source().map(SessionNodeSource::definition)
+                    == other.source().map(SessionNodeSource::definition)
+            }
+            None => false,
         };
 
-        // start all services and let those sync themselves
-        let node_run_tasks = other_nodes
+        // running nodes (and what those depend on) are kept as they are, together with
+        // nodes that depend only on unchanged ones: every other node is loaded again
+        let mut keep = HashMap::new();
+        for node in services.values() {
+            let mut closure = vec![];
+            Self::collect_dependencies(node, &mut closure);
+
+            if closure.iter().all(unchanged) {
+                keep.insert(node.name().to_owned(), node.clone());
+            }
+
+            if node.is_supervised() || transient.iter().any(|name| name == node.name()) {
+                for dependency in closure.into_iter() {
+                    keep.insert(dependency.name().to_owned(), dependency);
+                }
+            }
+        }
+
+        let mut outcome = ReloadOutcome {
+            skipped,
+            ..ReloadOutcome::default()
+        };
+        for name in names.iter() {
+            let node = services.get(name).unwrap();
+            match (
+                fresh.contains_key(name),
+                keep.contains_key(name),
+                unchanged(node),
+            ) {
+                (false, _, _) => outcome.removed.push(name.clone()),
+                (true, true, false) => outcome.needs_restart.push(name.clone()),
+                (true, false, false) => outcome.changed.push(name.clone()),
+                (true, _, true) => {}
+            }
+        }
+
+        // removed nodes still needed by running ones are stopped below
+        for name in outcome.removed.iter() {
+            keep.remove(name);
+        }
+
+        let mut loaded = keep;
+        let mut fresh_names = fresh.keys().cloned().collect::<Vec<_>>();
+        fresh_names.sort();
+        for name in fresh_names.iter() {
+            if let Err(err) = NodeServiceDescriptor::load_tree(&mut loaded, name, directories).await
+            {
+                return Err(SessionManagerError::ReloadFailed(format!("{name}: {err}")));
+            }
+
+            if !services.contains_key(name) {
+                outcome.added.push(name.clone());
+            }
+        }
+
+        let removed = outcome
+            .removed
             .iter()
-            .map(|node| {
-                let n = node.clone();
-                async move { SessionNode::run(n, false).await }
-            })
-            .collect::<JoinSet<_>>();
+            .filter_map(|name| services.get(name).cloned())
+            .collect::<Vec<_>>();
+
+        // nodes loaded again keep the runs of the ones they replace
+        for (name, node) in loaded.iter() {
+            if let Some(old) = services.get(name).filter(|old| !Arc::ptr_eq(old, node)) {
+                node.inherit_runs(old).await;
+            }
+        }
+
+        Self::adopt(&self.failures, &loaded);
+        *services = loaded;
+        drop(services);
+
+        *self.needs_restart.write().await = outcome.needs_restart.clone();
+
+        for node in removed.iter().filter(|node| node.is_supervised()) {
+            if let Err(err) =
+                SessionNode::issue_manual_action(node.clone(), ManualAction::Stop).await
+            {
+                eprintln!("Error stopping the removed {}: {err}", node.name());
+            }
+        }
+
+        for node in removed.iter() {
+            node.wait_for_supervision_end().await;
+        }
+
+        Ok(outcome)
+    }
+
+    /// Collects `node` and every node it (transitively) depends on in any way.
+    fn collect_dependencies(node: &Arc<SessionNode>, collected: &mut Vec<Arc<SessionNode>>) {
+        if collected.iter().any(|other| other.name() == node.name()) {
+            return;
+        }
+
+        collected.push(node.clone());
+
+        for dependency in node.dependencies().all() {
+            Self::collect_dependencies(dependency, collected);
+        }
+    }
+
+    /// Starts the failure handlers of `node`, that has failed for good: the name of the node
+    /// and the reason it has stopped are passed to those in environment variables.
+    async fn start_failure_handlers(&self, node: &SessionNode, reason: &SessionNodeStopReason) {
+        // nodes are not started while the session is being shut down
+        if self.shutting_down.load(Ordering::SeqCst) {
+            eprintln!("{} has failed ({reason}) while shutting down", node.name());
+            return;
+        }
+
+        for name in node.dependencies().on_failure().iter() {
+            eprintln!("{} has failed ({reason}): starting {name}", node.name());
+
+            let handler = match self.node_or_load(name).await {
+                Ok(handler) => handler,
+                Err(err) => {
+                    eprintln!("Error loading {name} to handle {}: {err}", node.name());
+                    continue;
+                }
+            };
+
+            handler.set_failure_environment(node.name(), reason);
+            match self.start(name).await {
+                Ok(outcome) if outcome.started() => {}
+                // already running: it won't pick up the environment
+                Ok(_) => handler.clear_failure_environment(),
+                Err(err) => {
+                    eprintln!("Error starting {name} to handle {}: {err}", node.name());
+                    handler.clear_failure_environment();
+                }
+            }
+        }
+    }
+
+    /// Returns a line describing the state of each node, sorted by name.
+    pub async fn dump(&self) -> Vec<String> {
+        let mut nodes = self
+            .services
+            .read()
+            .await
+            .values()
+            .cloned()
+            .collect::<Vec<_>>();
+        nodes.sort_by(|a, b| a.name().cmp(b.name()));
+
+        let mut lines = vec![];
+        for node in nodes.iter() {
+            lines.push(format!(
+                "{} ({}): {}",
+                node.name(),
+                node.kind(),
+                node.status().await
+            ));
+        }
+
+        lines
+    }
+
+    /// Stops every supervised node, the ones depending on others first, waiting for each
+    /// one to fully exit: the main node is stopped too, ending the session.
+    ///
+    /// Once `timeout` expires the process groups of the nodes still running are killed,
+    /// and those are given as much time again to exit.
+    /// The rescue target is never started while shutting down, and a shutdown requested
+    /// while another one is in progress waits for that one to end instead.
+    pub async fn shutdown(&self, timeout: Duration) {
+        if self.shutting_down.swap(true, Ordering::SeqCst) {
+            self.wait_for_shutdown().await;
+            return;
+        }
+
+        self.stop_all(timeout).await;
+    }
+
+    /// Shuts the session down in the background, as `shutdown` does, returning
+    /// false (and doing nothing) if the session is already being shut down.
+    pub fn request_shutdown(self: &Arc<Self>, timeout: Duration) -> bool {
+        if self.shutting_down.swap(true, Ordering::SeqCst) {
+            return false;
+        }
+
+        let manager = self.clone();
+        tokio::spawn(async move { manager.stop_all(timeout).await });
+
+        true
+    }
+
+    /// Waits for the shutdown in progress (if any) to end: the main node is stopped
+    /// among the first ones, so the session ends before every node has exited.
+    pub async fn wait_for_shutdown(&self) {
+        while self.shutting_down.load(Ordering::SeqCst) && !self.shut_down.load(Ordering::SeqCst) {
+            tokio::select! {
+                _ = sleep(Duration::from_millis(250)) => {},
+                _ = self.shutdown_notify.notified() => {},
+            };
+        }
+    }
+
+    async fn stop_all(&self, timeout: Duration) {
+        let mut nodes = self
+            .services
+            .read()
+            .await
+            .values()
+            .cloned()
+            .collect::<Vec<_>>();
+        nodes.sort_by(|a, b| a.name().cmp(b.name()));
+
+        let mut visited = vec![];
+        let mut order = vec![];
+        for node in nodes.iter() {
+            Self::collect_dependents(node, &mut visited, &mut order);
+        }
+
+        let stop_all = async {
+            // jobs are not run while the session is being shut down
+            let _dispatching = self.dispatcher.lock().await;
+
+            for node in order.iter().filter(|node| node.is_supervised()) {
+                eprintln!("Stopping {}", node.name());
+
+                if let Err(err) =
+                    SessionNode::issue_manual_action(node.clone(), ManualAction::Stop).await
+                {
+                    eprintln!("Error stopping {}: {err}", node.name());
+                }
+
+                node.wait_for_supervision_end().await;
+            }
+        };
+
+        if tokio::time::timeout(timeout, stop_all).await.is_err() {
+            eprintln!("Timed out shutting down the session: killing the remaining nodes");
+
+            for node in order.iter().filter(|node| node.is_supervised()) {
+                let killed = match node.kill(Signal::SIGKILL, KillWhom::Group).await {
+                    Err(ManualActionIssueError::NoProcessGroup) => {
+                        node.kill(Signal::SIGKILL, KillWhom::Main).await
+                    }
+                    result => result,
+                };
+
+                if let Err(err) = killed {
+                    eprintln!("Error killing {}: {err}", node.name());
+                }
+            }
+
+            let killed_all = async {
+                for node in order.iter() {
+                    node.wait_for_supervision_end().await;
+                }
+            };
+
+            if tokio::time::timeout(timeout, killed_all).await.is_err() {
+                eprintln!("Timed out waiting for the remaining nodes to be killed");
+            }
+        }
+
+        self.shut_down.store(true, Ordering::SeqCst);
+        self.shutdown_notify.notify_waiters();
+    }
+
+    /// Returns why the session has been switched to the rescue target, if it has.
+    pub async fn rescued(&self) -> Option<RescueDecision> {
+        self.rescued.read().await.clone()
+    }
+
+    /// Returns why the main node, that has exited, is to be considered failed:
+    /// either it has failed for good or it has been stopped while failing.
+    async fn failure_cause(main_node: &SessionNode) -> Option<String> {
+        let name = main_node.name();
+
+        match main_node.status().await {
+            SessionNodeStatus::Stopped {
+                restart: false,
+                reason,
+                ..
+            } if reason.is_failure() => Some(format!("{name} has failed: {reason}")),
+            // a stop request while waiting to be restarted leaves the last failed run in the history
+            SessionNodeStatus::Stopped {
+                reason: SessionNodeStopReason::ManuallyStopped,
+                ..
+            } => match main_node.history().await.last() {
+                Some(run) if run.reason().is_failure() => Some(format!(
+                    "{name} has been stopped after failing: {}",
+                    run.reason()
+                )),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Switches the session to the rescue target and runs it until it exits:
+    /// returns false if the rescue target cannot be loaded.
+    async fn rescue(&self, rescue: &String, cause: String) -> Result<bool, SessionManagerError> {
+        eprintln!("{cause}: switching the session to {rescue}");
+
+        let rescue_node = match self.node_or_load(rescue).await {
+            Ok(node) => node,
+            Err(err) => {
+                eprintln!("Error loading the rescue target {rescue}: {err}");
+                return Ok(false);
+            }
+        };
 
-        // wait for the target run to exit
-        let (main_node_res, other_nodes_res) = tokio::join!(
-            task::spawn(async move { SessionNode::run(main_node, true).await }),
-            node_run_tasks.join_all()
+        *self.rescued.write().await = Some(RescueDecision {
+            target: rescue.clone(),
+            cause,
+        });
+
+        self.isolate(rescue, None).await?;
+        *self.main.write().await = Some(rescue.clone());
+
+        // the rescue target takes the place of the main node
+        rescue_node.wait_for_supervision_end().await;
+
+        Ok(true)
+    }
+
+    /// Returns the nodes that have failed for good.
+    async fn failed(&self) -> Vec<String> {
+        let mut failed = vec![];
+        for node in self.services.read().await.values() {
+            if let SessionNodeStatus::Stopped {
+                restart: false,
+                reason,
+                ..
+            } = node.status().await
+            {
+                if reason.is_failure() {
+                    failed.push(node.name().to_owned());
+                }
+            }
+        }
+        failed.sort();
+
+        failed
+    }
+
+    /// Runs the target until it exits: only the target and its dependencies are started,
+    /// every other loaded service waits to be started on demand.
+    ///
+    /// If the target fails the session is switched to the rescue target, and that
+    /// is then run until it exits in place of the target.
+    pub async fn run(&self, target: &String) -> Result<RunOutcome, SessionManagerError> {
+        let main_node = self.node(target).await?;
+        *self.main.write().await = Some(target.clone());
+
+        // start required and wanted dependencies (stopping conflicting nodes, as start does)
+        // and let those sync themselves: every start is queued before any of them runs,
+        // so that nodes ordered after a later one wait for it
+        let mut queued = vec![];
+        for dependency in main_node.dependencies().pulled_in() {
+            let name = dependency.name().to_owned();
+            let transaction = match self.transaction(&name, JobKind::Start).await {
+                Ok(transaction) => transaction,
+                Err(err) => {
+                    eprintln!("Error starting {name} required by {target}: {err}");
+                    continue;
+                }
+            };
+            match self.queue(transaction.as_slice(), true).await {
+                Ok(ids) => queued.push((name, ids)),
+                Err(err) => eprintln!("Error starting {name} required by {target}: {err}"),
+            }
+        }
+        for (name, ids) in queued {
+            if let Err(err) = self.run_transaction(ids).await {
+                eprintln!("Error starting {name} required by {target}: {err}");
+            }
+        }
+
+        // wait for the session to end (rescue target included),
+        // starting failure handlers in the meantime
+        let (finished, mut running) = oneshot::channel::<()>();
+        let (session, _) = tokio::join!(
+            async {
+                let session = self.run_session(&main_node).await;
+                let _ = finished.send(());
+
+                session
+            },
+            async {
+                let mut failures = self.failures_receiver.lock().await;
+                loop {
+                    tokio::select! {
+                        Some((node, reason)) = failures.recv() => {
+                            self.start_failure_handlers(&node, &reason).await
+                        },
+                        _ = &mut running => break,
+                    }
+                }
+            }
         );
+        let (main, end) = session?;
 
-        Ok(())
+        Ok(RunOutcome {
+            main,
+            failed: self.failed().await,
+            end,
+        })
+    }
+    /// Runs the main node and, if it fails, the rescue target taking its place.
+    async fn run_session(
+        &self,
+        main_node: &Arc<SessionNode>,
+    ) -> Result<(RunResult, SessionEnd), SessionManagerError> {
+        let target = main_node.name();
+        let main = SessionNode::run(main_node.clone(), true).await;
+
+        let cause = Self::failure_cause(main_node).await;
+        let mut end = match (&cause, main_node.status().await) {
+            (Some(_), _) => SessionEnd::Failed,
+            (
+                None,
+                SessionNodeStatus::Stopped {
+                    reason: SessionNodeStopReason::ManuallyStopped,
+                    ..
+                },
+            ) => SessionEnd::Stopped,
+            (None, _) => SessionEnd::Completed,
+        };
+
+        let rescue = self
+            .rescue
+            .as_ref()
+            .filter(|rescue| *rescue != target)
+            .filter(|_| !self.shutting_down.load(Ordering::SeqCst));
+        if let (Some(cause), Some(rescue)) = (cause, rescue) {
+            if self.rescue(rescue, cause).await? {
+                end = SessionEnd::Rescued;
+            }
+        }
+
+        Ok((main, end))
     }
 }
@@ -18,8 +18,17 @@
 */
 
 use std::{
-    collections::HashMap, ops::Deref, path::PathBuf, process::ExitStatus, sync::Arc,
-    time::Duration, u64,
+    collections::{HashMap, VecDeque},
+    fmt,
+    ops::Deref,
+    os::unix::process::CommandExt,
+    path::PathBuf,
+    process::ExitStatus,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, OnceLock, Weak,
+    },
+    time::{Duration, SystemTime},
 };
 
 use thiserror::Error;
@@ -27,16 +36,20 @@ use tokio::{
     fs::File,
     io::AsyncWriteExt,
     process::Command,
-    sync::{Notify, RwLock},
+    sync::{mpsc::UnboundedSender, Notify, RwLock},
     task::JoinSet,
     time::{self, sleep, Instant},
 };
 
 use crate::{
-    errors::{NodeDependencyError, NodeDependencyResult},
+    errors::{NodeDependencyError, NodeDependencyResult, NodeHookError},
+    rusage::{self, ResourceUsage},
     signal::Signal,
 };
 
+/// Number of past runs kept in the exit history of each node.
+const HISTORY_LENGTH: usize = 16;
+
 #[derive(Debug)]
 pub struct SessionNodeRestart {
     max_times: u64,
@@ -73,12 +86,400 @@ impl Default for SessionNodeRestart {
     }
 }
 
-#[derive(Debug, Copy, Clone)]
+/// An additional command executed as part of a node lifecycle.
+#[derive(Debug, Clone)]
+pub struct SessionNodeCommand {
+    cmd: String,
+    args: Vec<String>,
+}
+
+impl SessionNodeCommand {
+    pub fn new(cmd: String, args: Vec<String>) -> Self {
+        Self { cmd, args }
+    }
+
+    pub fn cmd(&self) -> &str {
+        self.cmd.as_str()
+    }
+
+    pub fn args(&self) -> &[String] {
+        self.args.as_slice()
+    }
+
+    /// Returns the arguments with `$MAINPID` (or `${MAINPID}`) replaced by `pid`,
+    /// as stop and reload commands are not run through a shell.
+    pub fn args_with_main_pid(&self, pid: i32) -> Vec<String> {
+        let pid = format!("{pid}");
+
+        self.args
+            .iter()
+            .map(|arg| arg.replace("${MAINPID}", &pid).replace("$MAINPID", &pid))
+            .collect()
+    }
+}
+
+/// Where the definition of a node comes from: the file that has been loaded,
+/// the lower-priority files with the same name it has shadowed, the
+/// drop-in overrides applied to it (in the order they have been applied)
+/// and the files it extends (from the most generic one).
+///
+/// The resulting definition, with every file merged, is kept to detect changes.
+#[derive(Debug, Clone)]
+pub struct SessionNodeSource {
+    path: PathBuf,
+    shadowed: Vec<PathBuf>,
+    dropins: Vec<PathBuf>,
+    extends: Vec<PathBuf>,
+    definition: serde_json::Value,
+}
+
+impl SessionNodeSource {
+    pub fn new(
+        path: PathBuf,
+        shadowed: Vec<PathBuf>,
+        dropins: Vec<PathBuf>,
+        extends: Vec<PathBuf>,
+        definition: serde_json::Value,
+    ) -> Self {
+        Self {
+            path,
+            shadowed,
+            dropins,
+            extends,
+            definition,
+        }
+    }
+
+    pub fn definition(&self) -> &serde_json::Value {
+        &self.definition
+    }
+
+    pub fn extends(&self) -> &[PathBuf] {
+        self.extends.as_slice()
+    }
+
+    pub fn dropins(&self) -> &[PathBuf] {
+        self.dropins.as_slice()
+    }
+
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    pub fn shadowed(&self) -> &[PathBuf] {
+        self.shadowed.as_slice()
+    }
+}
+
+/// How a running node is brought down: the stop command (if any) is tried first,
+/// then the stop signal and, as a last resort, SIGKILL. Each attempt is given
+/// `timeout` for the process to exit before moving on to the next one.
+#[derive(Debug)]
+pub struct SessionNodeStop {
+    signal: Signal,
+    cmd: Option<SessionNodeCommand>,
+    timeout: Duration,
+}
+
+impl SessionNodeStop {
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+    pub fn new(signal: Signal, cmd: Option<SessionNodeCommand>, timeout: Duration) -> Self {
+        Self {
+            signal,
+            cmd,
+            timeout,
+        }
+    }
+
+    pub fn signal(&self) -> Signal {
+        self.signal
+    }
+
+    pub fn cmd(&self) -> Option<&SessionNodeCommand> {
+        self.cmd.as_ref()
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+}
+
+impl Default for SessionNodeStop {
+    fn default() -> Self {
+        Self {
+            signal: Signal::SIGTERM,
+            cmd: None,
+            timeout: Self::DEFAULT_TIMEOUT,
+        }
+    }
+}
+
+/// How a running node is asked to reload its configuration without being restarted:
+/// when both are available the reload command is preferred over the reload signal.
+#[derive(Debug, Default)]
+pub struct SessionNodeReload {
+    signal: Option<Signal>,
+    cmd: Option<SessionNodeCommand>,
+}
+
+impl SessionNodeReload {
+    pub fn new(signal: Option<Signal>, cmd: Option<SessionNodeCommand>) -> Self {
+        Self { signal, cmd }
+    }
+
+    pub fn signal(&self) -> Option<Signal> {
+        self.signal
+    }
+
+    pub fn cmd(&self) -> Option<&SessionNodeCommand> {
+        self.cmd.as_ref()
+    }
+
+    pub fn is_supported(&self) -> bool {
+        self.signal.is_some() || self.cmd.is_some()
+    }
+}
+
+/// Commands executed around the node process: pre-start ones are run
+/// before spawning the process, and if any of them fails the process is not started,
+/// post-start ones right after the process is spawned and post-stop ones after the
+/// process has exited (or failed to start).
+#[derive(Debug, Default)]
+pub struct SessionNodeHooks {
+    start_pre: Vec<SessionNodeCommand>,
+    start_post: Vec<SessionNodeCommand>,
+    stop_post: Vec<SessionNodeCommand>,
+}
+
+impl SessionNodeHooks {
+    pub fn new(
+        start_pre: Vec<SessionNodeCommand>,
+        start_post: Vec<SessionNodeCommand>,
+        stop_post: Vec<SessionNodeCommand>,
+    ) -> Self {
+        Self {
+            start_pre,
+            start_post,
+            stop_post,
+        }
+    }
+
+    pub fn start_pre(&self) -> &[SessionNodeCommand] {
+        self.start_pre.as_slice()
+    }
+
+    pub fn start_post(&self) -> &[SessionNodeCommand] {
+        self.start_post.as_slice()
+    }
+
+    pub fn stop_post(&self) -> &[SessionNodeCommand] {
+        self.stop_post.as_slice()
+    }
+}
+
+/// Other nodes a node depends on, by kind of relation:
+/// required nodes are started together with the node and the node fails (or is stopped)
+/// when any of them fails for good, wanted nodes are started together with the node
+/// but their failure is ignored, and the node is started only once the nodes it is
+/// ordered after are up and running (or have failed for good).
+///
+/// A node bound to another one is started together with it, after it, and is stopped
+/// whenever that one exits for good; a node part of another one follows the stop and
+/// restart requests of that one. Both are restarted whenever the other one is.
+///
+/// Nodes the node is part of, conflicting nodes and failure handlers are referenced by name
+/// as those relations usually go both ways (a target wanting the nodes that are part of it,
+/// a handler ordered after the node it handles the failure of): conflicting nodes are
+/// stopped before the node is started.
+///
+/// What happens when a required dependency fails for good is decided by `on_dependency_failure`,
+/// while the `on_failure` handlers are started whenever the node itself fails for good.
+#[derive(Debug, Default)]
+pub struct SessionNodeDependencies {
+    requires: Vec<Arc<SessionNode>>,
+    wants: Vec<Arc<SessionNode>>,
+    after: Vec<Arc<SessionNode>>,
+    binds_to: Vec<Arc<SessionNode>>,
+    part_of: Vec<String>,
+    conflicts: Vec<String>,
+    on_failure: Vec<String>,
+    on_dependency_failure: DependencyFailurePolicy,
+}
+
+impl SessionNodeDependencies {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        requires: Vec<Arc<SessionNode>>,
+        wants: Vec<Arc<SessionNode>>,
+        after: Vec<Arc<SessionNode>>,
+        binds_to: Vec<Arc<SessionNode>>,
+        part_of: Vec<String>,
+        conflicts: Vec<String>,
+        on_failure: Vec<String>,
+        on_dependency_failure: DependencyFailurePolicy,
+    ) -> Self {
+        Self {
+            requires,
+            wants,
+            after,
+            binds_to,
+            part_of,
+            conflicts,
+            on_failure,
+            on_dependency_failure,
+        }
+    }
+
+    pub fn requires(&self) -> &[Arc<SessionNode>] {
+        self.requires.as_slice()
+    }
+
+    pub fn wants(&self) -> &[Arc<SessionNode>] {
+        self.wants.as_slice()
+    }
+
+    pub fn after(&self) -> &[Arc<SessionNode>] {
+        self.after.as_slice()
+    }
+
+    pub fn binds_to(&self) -> &[Arc<SessionNode>] {
+        self.binds_to.as_slice()
+    }
+
+    pub fn part_of(&self) -> &[String] {
+        self.part_of.as_slice()
+    }
+
+    pub fn conflicts(&self) -> &[String] {
+        self.conflicts.as_slice()
+    }
+
+    pub fn on_failure(&self) -> &[String] {
+        self.on_failure.as_slice()
+    }
+
+    pub fn on_dependency_failure(&self) -> DependencyFailurePolicy {
+        self.on_dependency_failure
+    }
+
+    /// Returns the nodes that are started together with the node:
+    /// the required, wanted and bound ones.
+    pub fn pulled_in(&self) -> impl Iterator<Item = &Arc<SessionNode>> {
+        self.requires
+            .iter()
+            .chain(self.wants.iter())
+            .chain(self.binds_to.iter())
+    }
+
+    /// Returns every node the node depends on in any way (possibly more than once).
+    pub fn all(&self) -> impl Iterator<Item = &Arc<SessionNode>> {
+        self.pulled_in().chain(self.after.iter())
+    }
+
+    fn contains(nodes: &[Arc<SessionNode>], name: &str) -> bool {
+        nodes.iter().any(|node| node.name == name)
+    }
+}
+
+/// How a node reacts to one of its required dependencies failing for good.
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub enum DependencyFailurePolicy {
+    /// The failure is ignored and the node is started (or keeps running) anyway
+    StartAnyway,
+    /// The node is not started (or is stopped) and marked as failed
+    #[default]
+    Fail,
+    /// The node is not started (or is stopped) until the dependency is restarted
+    Wait,
+}
+
+impl fmt::Display for DependencyFailurePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DependencyFailurePolicy::StartAnyway => write!(f, "start-anyway"),
+            DependencyFailurePolicy::Fail => write!(f, "fail"),
+            DependencyFailurePolicy::Wait => write!(f, "wait"),
+        }
+    }
+}
+
+impl TryFrom<&str> for DependencyFailurePolicy {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "start-anyway" => Ok(DependencyFailurePolicy::StartAnyway),
+            "fail" => Ok(DependencyFailurePolicy::Fail),
+            "wait" => Ok(DependencyFailurePolicy::Wait),
+            _ => Err(format!("Invalid dependency failure policy: {value}")),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub enum SessionNodeStopReason {
     Completed(ExitStatus),
     Errored, /*(IOError)*/
     ManuallyStopped,
     ManuallyRestarted,
+    /// The named required dependency has failed for good
+    DependencyFailed(String),
+}
+
+impl SessionNodeStopReason {
+    /// Returns whether the node has stopped because of a failure
+    /// rather than by completing successfully or by request.
+    pub fn is_failure(&self) -> bool {
+        match self {
+            SessionNodeStopReason::Completed(status) => !status.success(),
+            SessionNodeStopReason::Errored | SessionNodeStopReason::DependencyFailed(_) => true,
+            SessionNodeStopReason::ManuallyStopped | SessionNodeStopReason::ManuallyRestarted => {
+                false
+            }
+        }
+    }
+}
+
+impl fmt::Display for SessionNodeStopReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SessionNodeStopReason::Completed(status) => write!(f, "completed ({status})"),
+            SessionNodeStopReason::Errored => write!(f, "errored"),
+            SessionNodeStopReason::ManuallyStopped => write!(f, "manually stopped"),
+            SessionNodeStopReason::ManuallyRestarted => write!(f, "manually restarted"),
+            SessionNodeStopReason::DependencyFailed(culprit) => {
+                write!(f, "dependency {culprit} failed")
+            }
+        }
+    }
+}
+
+/// A single past execution of a node, as recorded in its exit history.
+#[derive(Debug, Clone)]
+pub struct SessionNodeRun {
+    started: SystemTime,
+    stopped: SystemTime,
+    reason: SessionNodeStopReason,
+    usage: Option<ResourceUsage>,
+}
+
+impl SessionNodeRun {
+    pub fn started(&self) -> SystemTime {
+        self.started
+    }
+
+    pub fn stopped(&self) -> SystemTime {
+        self.stopped
+    }
+
+    pub fn reason(&self) -> &SessionNodeStopReason {
+        &self.reason
+    }
+
+    pub fn usage(&self) -> Option<ResourceUsage> {
+        self.usage
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -88,6 +489,14 @@ pub enum SessionNodeStatus {
         pid: i32,
         pending: Option<ManualAction>,
     },
+    /// A target has its dependencies satisfied: it has no process of its own.
+    Reached {
+        pending: Option<ManualAction>,
+    },
+    /// Waiting for the named required dependency, that has failed, to be restarted.
+    Waiting {
+        culprit: String,
+    },
     Stopped {
         time: time::Instant,
         restart: bool,
@@ -95,6 +504,25 @@ pub enum SessionNodeStatus {
     },
 }
 
+impl fmt::Display for SessionNodeStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SessionNodeStatus::Ready => write!(f, "ready"),
+            SessionNodeStatus::Running { pid, .. } => write!(f, "running (pid {pid})"),
+            SessionNodeStatus::Reached { .. } => write!(f, "reached"),
+            SessionNodeStatus::Waiting { culprit } => {
+                write!(f, "waiting for {culprit} to be restarted")
+            }
+            SessionNodeStatus::Stopped {
+                restart: true,
+                reason,
+                ..
+            } => write!(f, "stopped: {reason} (restarting)"),
+            SessionNodeStatus::Stopped { reason, .. } => write!(f, "stopped: {reason}"),
+        }
+    }
+}
+
 pub enum SessionStalledReason {
     RestartedTooManyTimes,
     TerminatedSuccessfully,
@@ -106,20 +534,60 @@ pub enum SessionStalledReason {
 pub enum SessionNodeType {
     OneShot,
     Service,
+    /// A synchronization point grouping other nodes, without a process of its own.
+    Target,
+}
+
+impl fmt::Display for SessionNodeType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SessionNodeType::OneShot => write!(f, "oneshot"),
+            SessionNodeType::Service => write!(f, "service"),
+            SessionNodeType::Target => write!(f, "target"),
+        }
+    }
 }
 
 #[derive(Copy, Clone, PartialEq, Debug)]
 pub enum ManualAction {
     Restart,
     Stop,
+    Reload,
+}
+
+enum ForcedAction {
+    ForcefullyRestart,
+    ForcefullyStop,
 }
 
+#[derive(Debug)]
 pub enum RunResult {
     NeverRun,
     Exited(ExitStatus),
     Error,
 }
 
+/// Which processes of a node receive a signal sent by the user.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum KillWhom {
+    /// Only the main process of the node
+    Main,
+    /// Every process in the process group of the node
+    Group,
+}
+
+impl TryFrom<&str> for KillWhom {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "main" => Ok(KillWhom::Main),
+            "group" => Ok(KillWhom::Group),
+            _ => Err(format!("Invalid kill target: {value}")),
+        }
+    }
+}
+
 #[derive(Error, Copy, Clone, PartialEq, Debug)]
 pub enum ManualActionIssueError {
     #[error("Error performing the requested action: action pending already")]
@@ -127,6 +595,21 @@ pub enum ManualActionIssueError {
 
     #[error("Error sending the termination signal: {0}")]
     CannotSendSignal(i32),
+
+    #[error("Error performing the requested action: reload not supported")]
+    ReloadNotSupported,
+
+    #[error("Error performing the requested action: not running")]
+    NotRunning,
+
+    #[error("Error performing the requested action: process group not owned by the node")]
+    NoProcessGroup,
+
+    #[error("Error running the reload command: {0}")]
+    CannotRunReloadCommand(i32),
+
+    #[error("Reload command terminated with exit code {0:?}")]
+    ReloadCommandFailed(Option<i32>),
 }
 
 #[derive(Debug)]
@@ -134,32 +617,52 @@ pub struct SessionNode {
     name: String,
     kind: SessionNodeType,
     pidfile: Option<PathBuf>,
-    stop_signal: Signal,
+    stop: SessionNodeStop,
     restart: SessionNodeRestart,
     cmd: String,
     args: Vec<String>,
-    dependencies: Vec<Arc<SessionNode>>,
+    dependencies: SessionNodeDependencies,
+    dependents: std::sync::RwLock<Vec<Weak<SessionNode>>>,
     status: Arc<RwLock<SessionNodeStatus>>,
     status_notify: Arc<Notify>,
     environment: HashMap<String, String>,
+    hooks: SessionNodeHooks,
+    reload: SessionNodeReload,
+    source: Option<SessionNodeSource>,
+    history: Arc<RwLock<VecDeque<SessionNodeRun>>>,
+    supervised: AtomicBool,
+    stop_requested: AtomicBool,
+    start_pending: AtomicBool,
+    failed_dependency: std::sync::Mutex<Option<String>>,
+    failure_environment: std::sync::Mutex<Vec<(String, String)>>,
+    failure_reporter: OnceLock<UnboundedSender<NodeFailure>>,
 }
 
+/// A node that has failed for good, together with the reason it has stopped:
+/// reported so that its failure handlers can be started.
+pub(crate) type NodeFailure = (Arc<SessionNode>, SessionNodeStopReason);
+
 fn assert_send_sync<T: Send + Sync>() {}
 
 impl SessionNode {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         name: String,
         kind: SessionNodeType,
         pidfile: Option<PathBuf>,
         cmd: String,
         args: Vec<String>,
-        stop_signal: Signal,
+        stop: SessionNodeStop,
         restart: SessionNodeRestart,
-        dependencies: Vec<Arc<SessionNode>>,
+        dependencies: SessionNodeDependencies,
         environment: HashMap<String, String>,
+        hooks: SessionNodeHooks,
+        reload: SessionNodeReload,
+        source: Option<SessionNodeSource>,
     ) -> Self {
         let status = Arc::new(RwLock::new(SessionNodeStatus::Ready));
         let status_notify = Arc::new(Notify::new());
+        let history = Arc::new(RwLock::new(VecDeque::with_capacity(HISTORY_LENGTH)));
 
         Self {
             name,
@@ -168,181 +671,394 @@ impl SessionNode {
             cmd,
             args,
             restart,
-            stop_signal,
+            stop,
             dependencies,
+            dependents: std::sync::RwLock::new(Vec::new()),
             status,
             status_notify,
             environment,
+            hooks,
+            reload,
+            source,
+            history,
+            supervised: AtomicBool::new(false),
+            stop_requested: AtomicBool::new(false),
+            start_pending: AtomicBool::new(false),
+            failed_dependency: std::sync::Mutex::new(None),
+            failure_environment: std::sync::Mutex::new(Vec::new()),
+            failure_reporter: OnceLock::new(),
         }
     }
 
-    pub async fn run(node: Arc<SessionNode>, main: bool) -> RunResult {
-        assert_send_sync::<Arc<SessionNode>>();
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
 
-        // Store environments at the beginning and reuse them later to ensure no bad env is carried over
-        let environment = std::env::vars().collect::<Vec<_>>();
+    pub fn kind(&self) -> SessionNodeType {
+        self.kind
+    }
 
-        let name = node.name.clone();
+    pub fn cmd(&self) -> &str {
+        self.cmd.as_str()
+    }
 
-        let mut restarted: u64 = 0;
+    pub fn args(&self) -> &[String] {
+        self.args.as_slice()
+    }
 
-        loop {
-            restarted += 1;
-            let will_restart_if_failed = restarted <= node.restart.max_times();
+    pub fn environment(&self) -> &HashMap<String, String> {
+        &self.environment
+    }
 
-            // wait for dependencies to be up and running or failed for good
-            if node
-                .dependencies
-                .iter()
-                .map(|a| {
-                    let dep = a.clone();
-                    tokio::spawn(async move { Self::wait_for_dependency_satisfied(dep).await })
-                })
-                .collect::<JoinSet<_>>()
-                .join_all()
-                .await
-                .iter()
-                .any(|dep_res| dep_res.is_err())
-            {
-                // TODO: what if there is an error?
-            }
+    pub fn dependencies(&self) -> &SessionNodeDependencies {
+        &self.dependencies
+    }
 
-            // Prepare the command to execute: use the old set of environment variables
-            let mut command = Command::new(node.cmd.as_str());
-            command.args(node.args.as_slice());
-            command.env_clear();
-            for (key, val) in environment.iter() {
-                command.env(key, val);
-            }
+    /// Registers `dependent` as a node depending on this one: nodes only reference
+    /// their dependents weakly as those already reference this node.
+    pub(crate) fn add_dependent(&self, dependent: &Arc<SessionNode>) {
+        // a node loaded again replaces the one it has been loaded from
+        let mut dependents = self.dependents.write().unwrap();
+        dependents.retain(|other| {
+            other
+                .upgrade()
+                .is_some_and(|node| node.name != dependent.name)
+        });
+        dependents.push(Arc::downgrade(dependent));
+    }
 
-            for (key, val) in node.environment.iter() {
-                command.env(key, val);
-            }
+    /// Returns the loaded nodes depending on this one in any way.
+    pub fn dependents(&self) -> Vec<Arc<SessionNode>> {
+        self.dependents
+            .read()
+            .unwrap()
+            .iter()
+            .filter_map(Weak::upgrade)
+            .collect()
+    }
 
-            let mut node_status = node.status.write().await;
+    /// Returns whether either this node or `other` declares a conflict with the other one.
+    pub fn conflicts_with(&self, other: &SessionNode) -> bool {
+        self.dependencies.conflicts.contains(&other.name)
+            || other.dependencies.conflicts.contains(&self.name)
+    }
 
-            let spawn_res = command.spawn();
-            let Ok(mut child) = spawn_res else {
-                eprintln!(
-                    "Error spawning the child process: {}",
-                    spawn_res.unwrap_err()
-                );
+    /// Returns whether the node is being supervised: it is either running or about to.
+    pub fn is_supervised(&self) -> bool {
+        self.supervised.load(Ordering::SeqCst)
+    }
 
-                *node_status = SessionNodeStatus::Stopped {
-                    time: Instant::now(),
-                    restart: will_restart_if_failed,
-                    reason: SessionNodeStopReason::Errored, /*(err)*/
-                };
-                node.status_notify.notify_waiters();
+    /// Records whether a queued job is about to start this node.
+    pub(crate) fn set_start_pending(&self, pending: bool) {
+        if self.start_pending.swap(pending, Ordering::SeqCst) != pending {
+            self.status_notify.notify_waiters();
+        }
+    }
 
-                continue;
+    /// Waits until the supervision of this node ends: once a stopped node is
+    /// no longer supervised its process has fully exited.
+    pub async fn wait_for_supervision_end(&self) {
+        while self.is_supervised() {
+            tokio::select! {
+                _ = sleep(Duration::from_millis(250)) => {},
+                _ = self.status_notify.notified() => {},
             };
+        }
+    }
 
-            let Some(pid) = child.id() else {
-                // The PID cannot be found: kill the process by its handle
-                eprintln!("Error fetching pid for {name}");
-                child.kill().await.unwrap();
+    pub async fn status(&self) -> SessionNodeStatus {
+        self.status.read().await.clone()
+    }
 
-                *node_status = SessionNodeStatus::Stopped {
-                    time: Instant::now(),
-                    restart: will_restart_if_failed,
-                    reason: SessionNodeStopReason::Errored, /*(err)*/
-                };
-                node.status_notify.notify_waiters();
+    /// Returns where this node has been loaded from, if it has been loaded from a file.
+    pub fn source(&self) -> Option<&SessionNodeSource> {
+        self.source.as_ref()
+    }
 
-                continue;
-            };
+    /// Returns past executions of this node, from the oldest to the most recent one.
+    pub async fn history(&self) -> Vec<SessionNodeRun> {
+        self.history.read().await.iter().cloned().collect()
+    }
 
-            if let Some(pidfile) = &node.pidfile {
-                match File::create(pidfile).await {
-                    Ok(mut pidfile) => match pidfile.write_all(format!("{pid}").as_bytes()).await {
-                        Ok(_) => {}
-                        Err(err) => {
-                            eprintln!("Error writing pidfile for {name}: {err}");
-                        }
-                    },
-                    Err(err) => {
-                        eprintln!("Error creating pidfile for {name}: {err}");
-                    }
-                }
-            }
+    /// Takes over the runs recorded by `other`, the node this one replaces,
+    /// together with how its last run has ended.
+    pub(crate) async fn inherit_runs(&self, other: &SessionNode) {
+        *self.history.write().await = other.history.read().await.clone();
 
-            // the process is now runnig: update the status and notify waiters
-            *node_status = SessionNodeStatus::Running {
-                pid: pid.try_into().unwrap(),
-                pending: None,
-            };
-            node.status_notify.notify_waiters();
+        let status = other.status.read().await.clone();
+        if let SessionNodeStatus::Stopped { .. } = status {
+            *self.status.write().await = status;
+        }
+    }
 
-            // while the process is awaited allows for other parts to get a hold of the status
-            // so that a stop or restart command can be issued
-            drop(node_status);
+    async fn record_run(&self, run: SessionNodeRun) {
+        let mut history = self.history.write().await;
+        if history.len() == HISTORY_LENGTH {
+            history.pop_front();
+        }
+        history.push_back(run);
+    }
 
-            enum ForcedAction {
-                ForcefullyRestart,
-                ForcefullyStop,
-            }
+    /// Starts the node unless it is already being supervised, returning whether it has been started:
+    /// required and wanted dependencies are started first (if they are not already).
+    pub async fn start(node: Arc<SessionNode>) -> bool {
+        for dependency in node.dependencies.pulled_in() {
+            Box::pin(Self::start(dependency.clone())).await;
+        }
 
-            let mut end_loop_action = None;
-            let mut success = false;
+        let mut status_guard = node.status.write().await;
+        node.stop_requested.store(false, Ordering::SeqCst);
+        if node.supervised.swap(true, Ordering::SeqCst) {
+            return false;
+        }
 
-            // here wait for child to exit or for the command to kill the process
-            // in the case user has requested program to exit use wait_for_dependency_stopped
-            // to wait until all dependencies are stopped
-            let mut last_exec_result = RunResult::NeverRun;
-            tokio::select! {
-                result = child.wait() => {
-                    last_exec_result = match result {
-                        Ok(result) => RunResult::Exited(result),
-                        Err(_err) => RunResult::Error,
-                    };
-                    let mut new_status = node.status.write().await;
-                    *new_status = match *(new_status) {
-                        SessionNodeStatus::Running { pid: _, pending } => match pending {
-                            Some(pending_action) => match pending_action {
-                                ManualAction::Restart => {
-                                    end_loop_action = Some(ForcedAction::ForcefullyRestart);
-                                    SessionNodeStatus::Stopped { time: Instant::now(), restart: will_restart_if_failed, reason: SessionNodeStopReason::Errored /*(err)*/ }
-                                },
-                                ManualAction::Stop => {
-                                    end_loop_action = Some(ForcedAction::ForcefullyStop);
-                                    SessionNodeStatus::Stopped { time: Instant::now(), restart: will_restart_if_failed, reason: SessionNodeStopReason::Errored /*(err)*/ }
-                                },
-                            },
-                            None => match &last_exec_result {
-                                RunResult::Exited(result) => {
-                                    success = result.success();
-                                    SessionNodeStatus::Stopped { time: Instant::now(), restart: !result.success() && will_restart_if_failed, reason: SessionNodeStopReason::Completed(*result) }
-                                },
-                                RunResult::Error => {
-                                    SessionNodeStatus::Stopped { time: Instant::now(), restart: will_restart_if_failed, reason: SessionNodeStopReason::Errored /*(err)*/ }
-                                },
-                                RunResult::NeverRun => unreachable!()
-                            }
-                        },
-                        _ => unreachable!(),
-                    }
-                },
-                // TODO: here await for the termination signal
-            };
+        *status_guard = SessionNodeStatus::Ready;
+        drop(status_guard);
+        node.status_notify.notify_waiters();
 
-            if let Some(pidfile) = &node.pidfile {
-                let _ = std::fs::remove_file(pidfile);
-            }
+        tokio::spawn(Self::run(node, false));
 
-            // the status has been changed: notify waiters
-            node.status_notify.notify_waiters();
+        true
+    }
 
-            match end_loop_action {
-                Some(todo) => match todo {
-                    ForcedAction::ForcefullyRestart => {
-                        // clear out the restart count to be coherent
+    /// Supervises the node until it is stopped for good, either because it has been
+    /// requested to stop or because it has terminated and won't be restarted.
+    pub async fn run(node: Arc<SessionNode>, main: bool) -> RunResult {
+        node.supervised.store(true, Ordering::SeqCst);
+
+        let result = Self::supervise(node.clone(), main).await;
+
+        // hold the status lock so that a concurrent start cannot miss the supervision ending
+        let status_guard = node.status.write().await;
+        node.supervised.store(false, Ordering::SeqCst);
+
+        let failure = match status_guard.deref() {
+            SessionNodeStatus::Stopped {
+                restart: false,
+                reason,
+                ..
+            } if reason.is_failure() => Some(reason.clone()),
+            _ => None,
+        };
+        drop(status_guard);
+
+        // failure handlers are started by whoever the failure is reported to
+        if let Some(reason) = failure {
+            if !node.dependencies.on_failure.is_empty() {
+                match node.failure_reporter.get() {
+                    Some(reporter) => {
+                        let _ = reporter.send((node.clone(), reason));
+                    }
+                    None => eprintln!("{} has failed ({reason}): nobody to report to", node.name),
+                }
+            }
+        }
+
+        result
+    }
+
+    /// Sets where failures of this node are reported to: only the first one set is used.
+    pub(crate) fn report_failures_to(&self, reporter: UnboundedSender<NodeFailure>) {
+        let _ = self.failure_reporter.set(reporter);
+    }
+
+    /// Describes the failure this node is started to handle: the name of the failed node
+    /// and the reason it has stopped are passed to the next run in environment variables.
+    pub(crate) fn set_failure_environment(&self, failed: &str, reason: &SessionNodeStopReason) {
+        *self.failure_environment.lock().unwrap() = vec![
+            (String::from("SESSIONRUNNER_FAILED_NODE"), failed.to_owned()),
+            (
+                String::from("SESSIONRUNNER_FAILURE_REASON"),
+                reason.to_string(),
+            ),
+        ];
+    }
+
+    /// Forgets the failure set by `set_failure_environment`, as the node has not been started.
+    pub(crate) fn clear_failure_environment(&self) {
+        self.failure_environment.lock().unwrap().clear();
+    }
+
+    async fn supervise(node: Arc<SessionNode>, main: bool) -> RunResult {
+        assert_send_sync::<Arc<SessionNode>>();
+
+        // Store environments at the beginning and reuse them later to ensure no bad env is carried over
+        let mut environment = std::env::vars().collect::<Vec<_>>();
+
+        // describe the failure this node is handling, if it has been started to handle one:
+        // it is taken so that a later start does not describe it again
+        environment.extend(std::mem::take(
+            &mut *node.failure_environment.lock().unwrap(),
+        ));
+
+        let name = node.name.clone();
+
+        let mut restarted: u64 = 0;
+
+        let mut last_exec_result = RunResult::NeverRun;
+
+        'supervision: loop {
+            // a stop might have been requested while the process was not running
+            if node.stop_requested.swap(false, Ordering::SeqCst) {
+                *node.status.write().await = SessionNodeStatus::Stopped {
+                    time: Instant::now(),
+                    restart: false,
+                    reason: SessionNodeStopReason::ManuallyStopped,
+                };
+                node.status_notify.notify_waiters();
+                node.propagate_exit(false, true).await;
+
+                if main {
+                    return Self::terminate_run(node.clone(), last_exec_result).await;
+                }
+
+                return last_exec_result;
+            }
+
+            restarted += 1;
+            let will_restart_if_failed = restarted <= node.restart.max_times();
+
+            loop {
+                // wait for the nodes this one is ordered after to be up and running or failed for good:
+                // a failure only matters if the dependency is also required, and that is checked below.
+                // Targets are reached only once every node they pull in is satisfied.
+                let pulled_in = match node.kind {
+                    SessionNodeType::Target => node.dependencies.pulled_in().collect::<Vec<_>>(),
+                    _ => vec![],
+                };
+                let dependencies = node
+                    .dependencies
+                    .after()
+                    .iter()
+                    .chain(node.dependencies.binds_to().iter())
+                    .chain(pulled_in)
+                    .map(|a| {
+                        let dep = a.clone();
+                        tokio::spawn(async move { Self::wait_for_dependency_satisfied(dep).await })
+                    })
+                    .collect::<JoinSet<_>>()
+                    .join_all();
+
+                tokio::select! {
+                    _ = dependencies => {},
+                    _ = node.wait_for_stop_request() => continue 'supervision,
+                };
+
+                let Some(culprit) = node.failed_requirement().await else {
+                    break;
+                };
+
+                if node.dependencies.on_dependency_failure() == DependencyFailurePolicy::Fail {
+                    eprintln!("{culprit} required by {name} has failed: not starting {name}");
+
+                    *node.status.write().await = SessionNodeStatus::Stopped {
+                        time: Instant::now(),
+                        restart: false,
+                        reason: SessionNodeStopReason::DependencyFailed(culprit),
+                    };
+                    node.status_notify.notify_waiters();
+
+                    if main {
+                        return Self::terminate_run(node.clone(), last_exec_result).await;
+                    }
+
+                    return last_exec_result;
+                }
+
+                // wait for the dependency to be restarted, then wait for it again
+                eprintln!(
+                    "{culprit} required by {name} has failed: waiting for it to be restarted"
+                );
+
+                *node.status.write().await = SessionNodeStatus::Waiting { culprit };
+                node.status_notify.notify_waiters();
+
+                tokio::select! {
+                    _ = node.wait_for_requirement_recovery() => {},
+                    _ = node.wait_for_stop_request() => continue 'supervision,
+                };
+            }
+
+            // post-stop hooks only follow a start that has actually begun:
+            // either pre-start hooks have run or a process has been spawned
+            let pre_hooks_ran = !node.hooks.start_pre().is_empty();
+
+            let end_loop_action;
+            (last_exec_result, end_loop_action) =
+                match Self::run_hooks(&node, &environment, node.hooks.start_pre()).await {
+                    // stopped while pre-start hooks were running
+                    Ok(()) if node.stop_requested.load(Ordering::SeqCst) => {
+                        if pre_hooks_ran {
+                            Self::run_stop_post_hooks(&node, &environment).await;
+                        }
+
+                        continue 'supervision;
+                    }
+                    Ok(()) => match node.kind {
+                        SessionNodeType::Target => Self::reach(&node).await,
+                        _ => Self::execute(&node, &environment, will_restart_if_failed).await,
+                    },
+                    Err(err) => {
+                        // a failing pre-start hook aborts the start as if the process failed
+                        eprintln!("Pre-start hook for {name} failed: {err}");
+
+                        *node.status.write().await = SessionNodeStatus::Stopped {
+                            time: Instant::now(),
+                            restart: will_restart_if_failed,
+                            reason: SessionNodeStopReason::Errored,
+                        };
+
+                        (RunResult::Error, None)
+                    }
+                };
+
+            let spawned = !matches!(
+                (node.kind, &last_exec_result),
+                (SessionNodeType::Target, _) | (_, RunResult::NeverRun)
+            );
+            if pre_hooks_ran || spawned {
+                Self::run_stop_post_hooks(&node, &environment).await;
+            }
+
+            let success =
+                matches!(&last_exec_result, RunResult::Exited(result) if result.success());
+
+            // the status has been changed: notify waiters
+            node.status_notify.notify_waiters();
+
+            let will_run_again = match &end_loop_action {
+                Some(ForcedAction::ForcefullyRestart) => true,
+                Some(ForcedAction::ForcefullyStop) => false,
+                None => will_restart_if_failed && !success,
+            };
+            node.propagate_exit(will_run_again, end_loop_action.is_some())
+                .await;
+
+            match end_loop_action {
+                Some(todo) => match todo {
+                    ForcedAction::ForcefullyRestart => {
+                        // clear out the restart count to be coherent
                         // with a restarted node that was halted due
                         // to too many restarts.
                         restarted = 0;
                         continue;
                     }
                     ForcedAction::ForcefullyStop => {
+                        // stopped because of a failed dependency: wait for it to be restarted
+                        if node.dependencies.on_dependency_failure()
+                            == DependencyFailurePolicy::Wait
+                            && matches!(
+                                *node.status.read().await,
+                                SessionNodeStatus::Stopped {
+                                    reason: SessionNodeStopReason::DependencyFailed(_),
+                                    ..
+                                }
+                            )
+                        {
+                            restarted = 0;
+                            continue;
+                        }
+
                         if main {
                             // TODO: flag the outcome: user has requested the
                             // node to be stopped, and this is the main node
@@ -350,17 +1066,19 @@ impl SessionNode {
                             return Self::terminate_run(node.clone(), last_exec_result).await;
                         }
 
-                        // trap the logic in an endless wait that
-                        // can only be escaped by restarting the node
-                        // or by the program termination (when main exits)
-                        todo!()
+                        // the node stays stopped until it is started again
+                        return last_exec_result;
                     }
                 },
                 None => {
                     // node exited (either successfully or with an error)
-                    // attempt to sleep before restarting it
+                    // attempt to sleep before restarting it: a stop request
+                    // interrupts the wait and is handled at the next iteration
                     if will_restart_if_failed && !success {
-                        sleep(node.restart.delay()).await;
+                        tokio::select! {
+                            _ = sleep(node.restart.delay()) => {},
+                            _ = node.wait_for_stop_request() => {},
+                        };
                         continue;
                     }
 
@@ -371,18 +1089,322 @@ impl SessionNode {
                         return Self::terminate_run(node.clone(), last_exec_result).await;
                     }
 
-                    // trap the logic in an endless wait that
-                    // can only be escaped by restarting the node
-                    // or by the program termination (when main exits)
-                    todo!()
+                    // the node stays stopped until it is started again
+                    return last_exec_result;
                 }
             }
         }
     }
 
+    /// Builds a command to be executed as part of this node: the given set of
+    /// environment variables is used, followed by the ones specific to this node.
+    fn command(&self, environment: &[(String, String)], cmd: &str, args: &[String]) -> Command {
+        let mut command = Command::new(cmd);
+        command.args(args);
+        command.env_clear();
+        for (key, val) in environment.iter() {
+            command.env(key, val);
+        }
+
+        for (key, val) in self.environment.iter() {
+            command.env(key, val);
+        }
+
+        command
+    }
+
+    /// Runs the given hooks one after the other, stopping at the first one that fails.
+    async fn run_hooks(
+        node: &Arc<SessionNode>,
+        environment: &[(String, String)],
+        hooks: &[SessionNodeCommand],
+    ) -> Result<(), NodeHookError> {
+        for hook in hooks.iter() {
+            let status = node
+                .command(environment, hook.cmd(), hook.args())
+                .status()
+                .await?;
+
+            if !status.success() {
+                return Err(NodeHookError::Failed(hook.cmd().to_owned(), status));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Runs the post-stop hooks once a start has ended: a failing one is only reported.
+    async fn run_stop_post_hooks(node: &Arc<SessionNode>, environment: &[(String, String)]) {
+        if let Err(err) = Self::run_hooks(node, environment, node.hooks.stop_post()).await {
+            eprintln!("Post-stop hook for {} failed: {err}", node.name);
+        }
+    }
+
+    /// Spawns the node process and waits for it to exit, updating the node status accordingly.
+    async fn execute(
+        node: &Arc<SessionNode>,
+        environment: &[(String, String)],
+        will_restart_if_failed: bool,
+    ) -> (RunResult, Option<ForcedAction>) {
+        let name = node.name.as_str();
+
+        // Prepare the command to execute: use the old set of environment variables.
+        // The process is spawned through std so that tokio never tracks (and reaps) it:
+        // wait4 below is the only one reaping it, even once the pid has been reused.
+        let mut command = node
+            .command(environment, node.cmd.as_str(), node.args.as_slice())
+            .into_std();
+
+        // every node runs in its own process group so that signals can be sent
+        // to the whole set of processes it has spawned
+        command.process_group(0);
+
+        let mut node_status = node.status.write().await;
+
+        // a stop requested while waiting for dependencies to be satisfied or while
+        // pre-start hooks were running: the process is not spawned at all
+        if node.stop_requested.swap(false, Ordering::SeqCst) {
+            *node_status = SessionNodeStatus::Stopped {
+                time: Instant::now(),
+                restart: false,
+                reason: SessionNodeStopReason::ManuallyStopped,
+            };
+
+            return (RunResult::NeverRun, Some(ForcedAction::ForcefullyStop));
+        }
+
+        let spawn_res = command.spawn();
+        let started = SystemTime::now();
+        let Ok(mut child) = spawn_res else {
+            eprintln!(
+                "Error spawning the child process: {}",
+                spawn_res.unwrap_err()
+            );
+
+            *node_status = SessionNodeStatus::Stopped {
+                time: Instant::now(),
+                restart: will_restart_if_failed,
+                reason: SessionNodeStopReason::Errored, /*(err)*/
+            };
+
+            return (RunResult::Error, None);
+        };
+
+        let Ok(pid) = i32::try_from(child.id()) else {
+            // The PID cannot be used: kill the process by its handle
+            eprintln!("Error fetching pid for {name}");
+            let _ = child.kill();
+            let _ = child.wait();
+
+            *node_status = SessionNodeStatus::Stopped {
+                time: Instant::now(),
+                restart: will_restart_if_failed,
+                reason: SessionNodeStopReason::Errored, /*(err)*/
+            };
+
+            return (RunResult::Error, None);
+        };
+
+        if let Some(pidfile) = &node.pidfile {
+            match File::create(pidfile).await {
+                Ok(mut pidfile) => match pidfile.write_all(format!("{pid}").as_bytes()).await {
+                    Ok(_) => {}
+                    Err(err) => {
+                        eprintln!("Error writing pidfile for {name}: {err}");
+                    }
+                },
+                Err(err) => {
+                    eprintln!("Error creating pidfile for {name}: {err}");
+                }
+            }
+        }
+
+        // the process is now runnig: update the status and notify waiters
+        *node_status = SessionNodeStatus::Running { pid, pending: None };
+        node.status_notify.notify_waiters();
+
+        // while the process is awaited allows for other parts to get a hold of the status
+        // so that a stop or restart command can be issued
+        drop(node_status);
+
+        // the child is reaped by wait4 so that resource usage can be collected:
+        // start waiting right away so that post-start hooks cannot delay that.
+        let mut wait = tokio::task::spawn_blocking(move || rusage::wait4(pid));
+
+        if let Err(err) = Self::run_hooks(node, environment, node.hooks.start_post()).await {
+            eprintln!("Post-start hook for {name} failed: {err}");
+        }
+
+        let mut end_loop_action = None;
+
+        // here wait for child to exit or for the command to kill the process
+        // in the case user has requested program to exit use wait_for_dependency_stopped
+        // to wait until all dependencies are stopped
+        let mut usage = None;
+        let wait_result = tokio::select! {
+            result = &mut wait => result,
+            culprit = node.wait_for_requirement_failure() => {
+                eprintln!("{culprit} required by {name} has failed: stopping {name}");
+
+                let mut status_guard = node.status.write().await;
+                if let SessionNodeStatus::Running { pid, pending: None } = *status_guard {
+                    *status_guard = SessionNodeStatus::Running {
+                        pid,
+                        pending: Some(ManualAction::Stop),
+                    };
+                    drop(status_guard);
+
+                    *node.failed_dependency.lock().unwrap() = Some(culprit);
+                    if let Err(err) = Self::terminate_process(node, pid) {
+                        eprintln!("Error stopping {name}: {err}");
+                    }
+                }
+
+                wait.await
+            },
+        };
+
+        let last_exec_result = match wait_result {
+            Ok(Ok((result, rusage))) => {
+                usage = Some(rusage);
+                RunResult::Exited(result)
+            }
+            Ok(Err(err)) => {
+                eprintln!("Error waiting for {name}: {err}");
+                RunResult::Error
+            }
+            Err(err) => {
+                eprintln!("Error joining the wait task for {name}: {err}");
+                RunResult::Error
+            }
+        };
+
+        let mut new_status = node.status.write().await;
+        *new_status = match *(new_status) {
+            SessionNodeStatus::Running { pid: _, pending } => match pending {
+                Some(pending_action) => match pending_action {
+                    ManualAction::Restart => {
+                        end_loop_action = Some(ForcedAction::ForcefullyRestart);
+                        SessionNodeStatus::Stopped {
+                            time: Instant::now(),
+                            restart: will_restart_if_failed,
+                            reason: SessionNodeStopReason::ManuallyRestarted,
+                        }
+                    }
+                    ManualAction::Stop => {
+                        end_loop_action = Some(ForcedAction::ForcefullyStop);
+                        SessionNodeStatus::Stopped {
+                            time: Instant::now(),
+                            restart: false,
+                            reason: match node.failed_dependency.lock().unwrap().take() {
+                                Some(culprit) => SessionNodeStopReason::DependencyFailed(culprit),
+                                None => SessionNodeStopReason::ManuallyStopped,
+                            },
+                        }
+                    }
+                    // reloading never terminates the process
+                    ManualAction::Reload => unreachable!(),
+                },
+                None => match &last_exec_result {
+                    RunResult::Exited(result) => SessionNodeStatus::Stopped {
+                        time: Instant::now(),
+                        restart: !result.success() && will_restart_if_failed,
+                        reason: SessionNodeStopReason::Completed(*result),
+                    },
+                    RunResult::Error => SessionNodeStatus::Stopped {
+                        time: Instant::now(),
+                        restart: will_restart_if_failed,
+                        reason: SessionNodeStopReason::Errored, /*(err)*/
+                    },
+                    RunResult::NeverRun => unreachable!(),
+                },
+            },
+            _ => unreachable!(),
+        };
+        drop(new_status);
+
+        let status = node.status().await;
+        if let SessionNodeStatus::Stopped { reason, .. } = status {
+            node.record_run(SessionNodeRun {
+                started,
+                stopped: SystemTime::now(),
+                reason,
+                usage,
+            })
+            .await;
+        }
+
+        if let Some(pidfile) = &node.pidfile {
+            let _ = std::fs::remove_file(pidfile);
+        }
+
+        (last_exec_result, end_loop_action)
+    }
+
+    /// Marks the target as reached and waits until it is requested to stop or restart,
+    /// or until one of its required dependencies fails for good.
+    async fn reach(node: &Arc<SessionNode>) -> (RunResult, Option<ForcedAction>) {
+        *node.status.write().await = SessionNodeStatus::Reached { pending: None };
+        node.status_notify.notify_waiters();
+
+        loop {
+            let mut status_guard = node.status.write().await;
+
+            let (reason, end_loop_action) = match *status_guard {
+                SessionNodeStatus::Reached {
+                    pending: Some(ManualAction::Restart),
+                } => (
+                    SessionNodeStopReason::ManuallyRestarted,
+                    ForcedAction::ForcefullyRestart,
+                ),
+                SessionNodeStatus::Reached {
+                    pending: Some(ManualAction::Stop),
+                } => (
+                    SessionNodeStopReason::ManuallyStopped,
+                    ForcedAction::ForcefullyStop,
+                ),
+                _ if node.stop_requested.swap(false, Ordering::SeqCst) => (
+                    SessionNodeStopReason::ManuallyStopped,
+                    ForcedAction::ForcefullyStop,
+                ),
+                _ => match node.failed_requirement().await {
+                    Some(culprit) => {
+                        eprintln!(
+                            "{culprit} required by {} has failed: stopping it",
+                            node.name
+                        );
+
+                        (
+                            SessionNodeStopReason::DependencyFailed(culprit),
+                            ForcedAction::ForcefullyStop,
+                        )
+                    }
+                    None => {
+                        drop(status_guard);
+
+                        tokio::select! {
+                            _ = sleep(Duration::from_millis(250)) => {},
+                            _ = node.status_notify.notified() => {},
+                        };
+
+                        continue;
+                    }
+                },
+            };
+
+            *status_guard = SessionNodeStatus::Stopped {
+                time: Instant::now(),
+                restart: matches!(end_loop_action, ForcedAction::ForcefullyRestart),
+                reason,
+            };
+
+            return (RunResult::NeverRun, Some(end_loop_action));
+        }
+    }
+
     async fn terminate_run(node: Arc<SessionNode>, result: RunResult) -> RunResult {
         node.dependencies
-            .iter()
+            .pulled_in()
             .map(|a| {
                 let dep = a.clone();
                 tokio::spawn(async move { Self::wait_for_dependency_stopped(dep).await })
@@ -400,24 +1422,29 @@ impl SessionNode {
         assert_send_sync::<Arc<SessionNode>>();
 
         loop {
-            match dependency.kind {
-                SessionNodeType::OneShot => {
-                    // TODO: here wait for it to be stopped
-                    // return OK(()) on success, Err() otherwise.
+            match (dependency.kind, dependency.status.read().await.deref()) {
+                // a node that is not being started (nor about to be) won't ever be up and running
+                (_, SessionNodeStatus::Ready)
+                    if !dependency.supervised.load(Ordering::SeqCst)
+                        && !dependency.start_pending.load(Ordering::SeqCst) =>
+                {
+                    return Ok(())
                 }
-                SessionNodeType::Service => match dependency.status.read().await.deref() {
-                    SessionNodeStatus::Ready => {}
-                    SessionNodeStatus::Running { pid: _, pending: _ } => return Ok(()),
+                (SessionNodeType::Service, SessionNodeStatus::Running { .. }) => return Ok(()),
+                (SessionNodeType::Target, SessionNodeStatus::Reached { .. }) => return Ok(()),
+                (
+                    SessionNodeType::OneShot,
                     SessionNodeStatus::Stopped {
-                        time: _,
-                        restart,
-                        reason: _,
-                    } => {
-                        if !*restart {
-                            return Err(NodeDependencyError::ServiceWontRestart);
-                        }
-                    }
-                },
+                        reason: SessionNodeStopReason::Completed(result),
+                        ..
+                    },
+                ) if result.success() => return Ok(()),
+                (_, SessionNodeStatus::Stopped { restart: false, .. })
+                    if !dependency.start_pending.load(Ordering::SeqCst) =>
+                {
+                    return Err(NodeDependencyError::ServiceWontRestart)
+                }
+                _ => {}
             }
 
             // wait for a signal to arrive to re-check or wait the timeout:
@@ -430,7 +1457,7 @@ impl SessionNode {
         }
     }
 
-    pub(crate) async fn wait_for_dependency_stopped(dependency: Arc<SessionNode>) {
+    pub(crate) async fn wait_for_dependency_stopped(_dependency: Arc<SessionNode>) {
         assert_send_sync::<Arc<SessionNode>>();
 
         // TODO: wait for the dependency to be stopped in order to exit cleanly
@@ -448,23 +1475,331 @@ impl SessionNode {
         false
         */
 
-        match *self.status.read().await {
-            SessionNodeStatus::Running { pid: _, pending: _ } => true,
-            _ => false,
+        matches!(
+            *self.status.read().await,
+            SessionNodeStatus::Running { pid: _, pending: _ } | SessionNodeStatus::Reached { .. }
+        )
+    }
+
+    /// Propagates the exit of this node to the nodes bound to or part of it: those are
+    /// restarted if this node will run again, otherwise bound ones are stopped and the
+    /// ones part of this node are stopped only if the exit has been requested.
+    ///
+    /// Nodes that are not supervised are never started here.
+    async fn propagate_exit(&self, will_run_again: bool, requested: bool) {
+        for dependent in self.dependents() {
+            let bound =
+                SessionNodeDependencies::contains(&dependent.dependencies.binds_to, &self.name);
+            let part = dependent.dependencies.part_of.contains(&self.name);
+
+            let action = match (bound, part) {
+                (false, false) => continue,
+                _ if will_run_again => ManualAction::Restart,
+                (true, _) => ManualAction::Stop,
+                (false, true) if requested => ManualAction::Stop,
+                (false, true) => continue,
+            };
+
+            if !dependent.is_supervised() {
+                continue;
+            }
+
+            let mut status_guard = dependent.status.write().await;
+            match *status_guard {
+                SessionNodeStatus::Running { pid, pending: None } => {
+                    *status_guard = SessionNodeStatus::Running {
+                        pid,
+                        pending: Some(action),
+                    };
+                    drop(status_guard);
+
+                    if let Err(err) = Self::terminate_process(&dependent, pid) {
+                        eprintln!(
+                            "Error propagating the exit of {} to {}: {err}",
+                            self.name, dependent.name
+                        );
+                    }
+                }
+                SessionNodeStatus::Reached { pending: None } => {
+                    *status_guard = SessionNodeStatus::Reached {
+                        pending: Some(action),
+                    };
+                    drop(status_guard);
+
+                    dependent.status_notify.notify_waiters();
+                }
+                // already stopping or restarting
+                SessionNodeStatus::Running {
+                    pending: Some(_), ..
+                }
+                | SessionNodeStatus::Reached { pending: Some(_) } => {}
+                // not running at the moment: it will wait for this node if it has to restart
+                _ => {
+                    drop(status_guard);
+
+                    if action == ManualAction::Stop {
+                        dependent.stop_requested.store(true, Ordering::SeqCst);
+                        dependent.status_notify.notify_waiters();
+                    }
+                }
+            }
         }
     }
 
+    /// Returns the name of a required dependency that has failed for good, if any:
+    /// failures are never reported to nodes that start anyway.
+    async fn failed_requirement(&self) -> Option<String> {
+        if self.dependencies.on_dependency_failure == DependencyFailurePolicy::StartAnyway {
+            return None;
+        }
+
+        for dependency in self
+            .dependencies
+            .requires
+            .iter()
+            .chain(self.dependencies.binds_to.iter())
+        {
+            if let SessionNodeStatus::Stopped {
+                restart: false,
+                reason,
+                ..
+            } = dependency.status.read().await.deref()
+            {
+                if reason.is_failure() {
+                    return Some(dependency.name.clone());
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Waits until a required dependency fails for good, returning its name.
+    async fn wait_for_requirement_failure(&self) -> String {
+        loop {
+            if let Some(culprit) = self.failed_requirement().await {
+                return culprit;
+            }
+
+            sleep(Duration::from_millis(250)).await;
+        }
+    }
+
+    /// Waits until no required dependency is failed anymore (because it has been restarted).
+    async fn wait_for_requirement_recovery(&self) {
+        while self.failed_requirement().await.is_some() {
+            sleep(Duration::from_millis(250)).await;
+        }
+    }
+
+    /// Waits until a stop is requested while the process of this node is not running.
+    async fn wait_for_stop_request(&self) {
+        while !self.stop_requested.load(Ordering::SeqCst) {
+            tokio::select! {
+                _ = sleep(Duration::from_millis(250)) => {},
+                _ = self.status_notify.notified() => {},
+            };
+        }
+    }
+
+    /// Waits until the process identified by `pid` is no longer running as part of
+    /// this node or `deadline` is reached, returning whether the process has exited.
+    async fn wait_for_exit(&self, pid: i32, deadline: Instant) -> bool {
+        loop {
+            match *self.status.read().await {
+                SessionNodeStatus::Running {
+                    pid: running_pid,
+                    pending: _,
+                } if running_pid == pid => {}
+                _ => return true,
+            }
+
+            if Instant::now() >= deadline {
+                return false;
+            }
+
+            tokio::select! {
+                _ = sleep(Duration::from_millis(250)) => {},
+                _ = self.status_notify.notified() => {},
+            };
+        }
+    }
+
+    /// Escalates the termination of the process identified by `pid`
+    /// according to the node stop settings: when `signal_sent` is true
+    /// the stop signal has been sent already and only SIGKILL is left.
+    async fn stop_process(node: Arc<SessionNode>, pid: i32, signal_sent: bool) {
+        let name = node.name.as_str();
+
+        if !signal_sent {
+            if let Some(exec_stop) = node.stop.cmd() {
+                let deadline = Instant::now() + node.stop.timeout();
+
+                let environment = std::env::vars().collect::<Vec<_>>();
+                let args = exec_stop.args_with_main_pid(pid);
+                let mut command = node.command(&environment, exec_stop.cmd(), &args);
+                command.env("MAINPID", format!("{pid}"));
+
+                match time::timeout_at(deadline, command.status()).await {
+                    Ok(Ok(status)) if status.success() => {}
+                    Ok(Ok(status)) => eprintln!("Stop command for {name} terminated with {status}"),
+                    Ok(Err(err)) => eprintln!("Error running the stop command for {name}: {err}"),
+                    Err(_) => eprintln!("Stop command for {name} timed out"),
+                }
+
+                if node.wait_for_exit(pid, deadline).await {
+                    return;
+                }
+            }
+
+            if let Err(err) = node.stop.signal().send_to(pid) {
+                eprintln!("Error sending {} to {name}: {err}", node.stop.signal());
+            }
+        }
+
+        if node
+            .wait_for_exit(pid, Instant::now() + node.stop.timeout())
+            .await
+        {
+            return;
+        }
+
+        // children are killed too: each node has a process group of its own
+        eprintln!("{name} did not stop in time: sending {}", Signal::SIGKILL);
+        let sent = match Self::process_group(pid) {
+            Some(pgid) => Signal::SIGKILL.send_to_group(pgid),
+            None => Signal::SIGKILL.send_to(pid),
+        };
+        if let Err(err) = sent {
+            eprintln!("Error sending {} to {name}: {err}", Signal::SIGKILL);
+        }
+    }
+
+    /// Asks the running process to reload its configuration using either the reload
+    /// command or the reload signal: the process is not restarted in either case.
+    async fn reload_process(node: Arc<SessionNode>) -> Result<(), ManualActionIssueError> {
+        if !node.reload.is_supported() {
+            return Err(ManualActionIssueError::ReloadNotSupported);
+        }
+
+        let pid = match *node.status.read().await {
+            SessionNodeStatus::Running { pid, pending: None } => pid,
+            SessionNodeStatus::Running {
+                pid: _,
+                pending: Some(_),
+            } => return Err(ManualActionIssueError::AlreadyPendingAction),
+            _ => return Err(ManualActionIssueError::NotRunning),
+        };
+
+        match (node.reload.cmd(), node.reload.signal()) {
+            (Some(exec_reload), _) => {
+                let environment = std::env::vars().collect::<Vec<_>>();
+                let args = exec_reload.args_with_main_pid(pid);
+                let mut command = node.command(&environment, exec_reload.cmd(), &args);
+                command.env("MAINPID", format!("{pid}"));
+
+                let status = command.status().await.map_err(|err| {
+                    ManualActionIssueError::CannotRunReloadCommand(
+                        err.raw_os_error().unwrap_or_default(),
+                    )
+                })?;
+
+                match status.success() {
+                    true => Ok(()),
+                    false => Err(ManualActionIssueError::ReloadCommandFailed(status.code())),
+                }
+            }
+            (None, Some(signal)) => signal
+                .send_to(pid)
+                .map_err(ManualActionIssueError::CannotSendSignal),
+            (None, None) => Err(ManualActionIssueError::ReloadNotSupported),
+        }
+    }
+
+    /// Sends `signal` to the running process (or its whole process group) without
+    /// altering the node status: the outcome is handled as if the signal came from elsewhere.
+    pub async fn kill(&self, signal: Signal, whom: KillWhom) -> Result<(), ManualActionIssueError> {
+        let SessionNodeStatus::Running { pid, pending: _ } = *self.status.read().await else {
+            return Err(ManualActionIssueError::NotRunning);
+        };
+
+        match whom {
+            KillWhom::Main => signal.send_to(pid),
+            KillWhom::Group => match Self::process_group(pid) {
+                Some(pgid) => signal.send_to_group(pgid),
+                None => return Err(ManualActionIssueError::NoProcessGroup),
+            },
+        }
+        .map_err(ManualActionIssueError::CannotSendSignal)
+    }
+
+    /// Returns the process group of `pid`, unless it is the one sessionrunner belongs to:
+    /// that group must never be signalled.
+    fn process_group(pid: i32) -> Option<i32> {
+        let pgid = unsafe { libc::getpgid(pid) };
+        if pgid <= 0 || pgid == unsafe { libc::getpgrp() } {
+            return None;
+        }
+
+        Some(pgid)
+    }
+
+    /// Starts terminating the process identified by `pid` once a pending action has been set:
+    /// without a stop command the signal is sent right away so that a failure can be
+    /// reported to the caller, the rest of the escalation happens in the background.
+    fn terminate_process(node: &Arc<SessionNode>, pid: i32) -> Result<(), ManualActionIssueError> {
+        let signal_sent = match node.stop.cmd() {
+            Some(_) => false,
+            None => {
+                node.stop
+                    .signal()
+                    .send_to(pid)
+                    .map_err(ManualActionIssueError::CannotSendSignal)?;
+
+                true
+            }
+        };
+
+        tokio::spawn(Self::stop_process(node.clone(), pid, signal_sent));
+
+        Ok(())
+    }
+
     pub async fn issue_manual_action(
         node: Arc<SessionNode>,
         action: ManualAction,
     ) -> Result<(), ManualActionIssueError> {
+        if action == ManualAction::Reload {
+            return Self::reload_process(node).await;
+        }
+
         let mut status_guard = node.status.write().await;
 
         match *status_guard {
-            SessionNodeStatus::Ready => match &action {
-                ManualAction::Restart => todo!(),
-                ManualAction::Stop => todo!(),
-            },
+            SessionNodeStatus::Ready
+            | SessionNodeStatus::Waiting { .. }
+            | SessionNodeStatus::Stopped { .. } => {
+                // requested while holding the status lock: execute checks it under the same
+                // lock right before spawning the process, so the request cannot be missed
+                if action == ManualAction::Stop {
+                    node.stop_requested.store(true, Ordering::SeqCst);
+                }
+
+                drop(status_guard);
+
+                match &action {
+                    // a node that is already supervised will be started on its own
+                    ManualAction::Restart => {
+                        Self::start(node).await;
+                    }
+                    ManualAction::Stop => {
+                        node.status_notify.notify_waiters();
+                    }
+                    ManualAction::Reload => unreachable!(),
+                }
+
+                Ok(())
+            }
             SessionNodeStatus::Running { pid, pending } => match pending {
                 Some(_) => Err(ManualActionIssueError::AlreadyPendingAction),
                 None => {
@@ -473,17 +1808,24 @@ impl SessionNode {
                         pending: Some(action),
                     };
 
-                    match node.stop_signal.send_to(pid) {
-                        Ok(_) => Ok(()),
-                        Err(err) => Err(ManualActionIssueError::CannotSendSignal(err)),
-                    }
+                    drop(status_guard);
+
+                    Self::terminate_process(&node, pid)
+                }
+            },
+            SessionNodeStatus::Reached { pending } => match pending {
+                Some(_) => Err(ManualActionIssueError::AlreadyPendingAction),
+                None => {
+                    *status_guard = SessionNodeStatus::Reached {
+                        pending: Some(action),
+                    };
+
+                    drop(status_guard);
+                    node.status_notify.notify_waiters();
+
+                    Ok(())
                 }
             },
-            SessionNodeStatus::Stopped {
-                time,
-                restart,
-                reason,
-            } => todo!(),
         }
     }
 }
@@ -0,0 +1,100 @@
+/*
+    login-ng A greeter written in rust that also supports autologin with systemd-homed
+    Copyright (C) 2024-2025  Denis Benato
+
+    This program is free software; you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation; either version 2 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License along
+    with this program; if not, write to the Free Software Foundation, Inc.,
+    51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
+*/
+
+use std::{
+    io::Error as IOError, os::unix::process::ExitStatusExt, process::ExitStatus, time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+/// Resources consumed by a process (and its reaped children) during its lifetime,
+/// as reported by the kernel when the process is reaped.
+#[derive(Serialize, Deserialize, Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct ResourceUsage {
+    user_time_us: u64,
+    system_time_us: u64,
+    max_rss_kb: u64,
+    voluntary_context_switches: u64,
+    involuntary_context_switches: u64,
+}
+
+fn timeval_to_us(tv: &libc::timeval) -> u64 {
+    (tv.tv_sec as u64) * 1_000_000 + (tv.tv_usec as u64)
+}
+
+impl From<&libc::rusage> for ResourceUsage {
+    fn from(value: &libc::rusage) -> Self {
+        Self {
+            user_time_us: timeval_to_us(&value.ru_utime),
+            system_time_us: timeval_to_us(&value.ru_stime),
+            max_rss_kb: value.ru_maxrss as u64,
+            voluntary_context_switches: value.ru_nvcsw as u64,
+            involuntary_context_switches: value.ru_nivcsw as u64,
+        }
+    }
+}
+
+impl ResourceUsage {
+    pub fn user_time(&self) -> Duration {
+        Duration::from_micros(self.user_time_us)
+    }
+
+    pub fn system_time(&self) -> Duration {
+        Duration::from_micros(self.system_time_us)
+    }
+
+    pub fn cpu_time(&self) -> Duration {
+        self.user_time() + self.system_time()
+    }
+
+    pub fn max_rss_kb(&self) -> u64 {
+        self.max_rss_kb
+    }
+
+    pub fn voluntary_context_switches(&self) -> u64 {
+        self.voluntary_context_switches
+    }
+
+    pub fn involuntary_context_switches(&self) -> u64 {
+        self.involuntary_context_switches
+    }
+}
+
+/// Blocks until the process identified by `pid` exits, reaping it and
+/// collecting its exit status together with the resources it consumed.
+///
+/// This has to be the only place the process is reaped: once `wait4` returns
+/// the kernel discards the process accounting information.
+pub fn wait4(pid: i32) -> Result<(ExitStatus, ResourceUsage), IOError> {
+    let mut status: libc::c_int = 0;
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+
+    loop {
+        let res = unsafe { libc::wait4(pid, &mut status, 0, &mut usage) };
+
+        if res == pid {
+            return Ok((ExitStatus::from_raw(status), ResourceUsage::from(&usage)));
+        }
+
+        let err = IOError::last_os_error();
+        if err.kind() != std::io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+}
@@ -85,19 +85,20 @@ pub struct GamescopeExecveRunner {
     gamescope_args: Vec<String>,
     shared_env: Vec<(String, String)>,
     socket: PathBuf,
+    #[allow(dead_code)]
     stats: PathBuf,
 }
 
 impl GamescopeExecveRunner {
     pub fn new(splitted: Vec<String>) -> Self {
-        let tmp_dir = PathBuf::from(match std::env::var("XDG_RUNTIME_DIR") {
+        let tmp_dir = match std::env::var("XDG_RUNTIME_DIR") {
             Ok(env) => PathBuf::from(mktemp_dir(env, "gamescope.XXXXXXX")),
             Err(err) => {
                 eprint!("Error in fetching XDG_RUNTIME_DIR: {err}");
 
                 PathBuf::from(mktemp_dir("/tmp/", "gamescope.XXXXXXX"))
             }
-        });
+        };
 
         let socket = tmp_dir.join("startup.socket");
         let stats = tmp_dir.join("stats.pipe");
@@ -21,8 +21,8 @@ pub(crate) fn find_program_path(program: &str) -> Result<String, Box<dyn Error>>
 
 pub(crate) fn execve_wrapper(
     prog: &CStr,
-    argv_data: &Vec<CStr>,
-    envp_data: &Vec<CStr>,
+    argv_data: &[CStr],
+    envp_data: &[CStr],
 ) -> Result<(), Box<dyn std::error::Error>> {
     let prog = prog.inner();
 
@@ -51,7 +51,7 @@ pub(crate) fn get_unix_username(uid: u32) -> Option<String> {
     unsafe {
         let mut result = std::ptr::null_mut();
         let amt = match libc::sysconf(libc::_SC_GETPW_R_SIZE_MAX) {
-            n if n < 0 => 512 as usize,
+            n if n < 0 => 512,
             n => n as usize,
         };
         let mut buf = Vec::with_capacity(amt);
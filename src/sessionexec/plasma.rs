@@ -70,7 +70,7 @@ impl Runner for PlasmaRunner {
             _ => println!("signal handler setup correctly, was previously {result}"),
         }
 
-        let mut exit_status = None;
+        let exit_status;
         loop {
             match child.try_wait() {
                 Ok(res) => match res {
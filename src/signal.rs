@@ -49,6 +49,16 @@ impl Signal {
 
         Ok(())
     }
+
+    pub fn send_to_group(&self, pgid: i32) -> Result<(), i32> {
+        let res = unsafe { libc::killpg(pgid, *self as i32) };
+
+        if res != 0 {
+            return Err(unsafe { *libc::__errno_location() });
+        }
+
+        Ok(())
+    }
 }
 
 impl TryFrom<&str> for Signal {
@@ -19,9 +19,22 @@
 
 use std::{collections::HashMap, path::PathBuf, sync::Arc, time::Duration};
 
-use tokio::{join, time::sleep};
+use tokio::{
+    join,
+    time::{sleep, timeout},
+};
 
-use crate::{desc::NodeServiceDescriptor, manager::SessionManager};
+use crate::{
+    desc::NodeServiceDescriptor,
+    errors::{NodeLoadingError, SessionManagerError},
+    job::{JobKind, JobState},
+    manager::{IsolateStep, SessionEnd, SessionManager, StopMode},
+    node::{
+        KillWhom, ManualActionIssueError, RunResult, SessionNodeStatus, SessionNodeStopReason,
+        SessionNodeType,
+    },
+    signal::Signal,
+};
 
 #[tokio::test]
 async fn test_not_found() {
@@ -67,12 +80,258 @@ async fn test_cyclic_deps() {
 
     match load_res {
         crate::errors::NodeLoadingError::CyclicDependency(dep) => {
-            assert_eq!(dep, String::from("default.service"))
+            assert_eq!(dep, String::from("default.service -> default.service"))
         }
         crate::errors::NodeLoadingError::IOError(_) => assert_eq!(1, 4),
         crate::errors::NodeLoadingError::FileNotFound(_) => assert_eq!(2, 4),
         crate::errors::NodeLoadingError::JSONError(_) => assert_eq!(3, 4),
         crate::errors::NodeLoadingError::InvalidKind(_) => assert_eq!(4, 4),
+        crate::errors::NodeLoadingError::InvalidSignal(_) => assert_eq!(5, 4),
+        crate::errors::NodeLoadingError::InvalidDropIn(_) => assert_eq!(6, 4),
+        crate::errors::NodeLoadingError::InvalidExtends(_) => assert_eq!(7, 4),
+        crate::errors::NodeLoadingError::CyclicExtends(_) => assert_eq!(8, 4),
+        crate::errors::NodeLoadingError::InvalidCommand(_) => assert_eq!(9, 4),
+        crate::errors::NodeLoadingError::InvalidPolicy(_) => assert_eq!(10, 4),
+    }
+}
+
+#[tokio::test]
+async fn test_signal_aliases() {
+    let load_path = PathBuf::from("test_data/test_signal_aliases");
+    assert!(load_path.exists());
+
+    let load_directoried = vec![load_path.clone()];
+
+    let default_service_name = String::from("default.service");
+
+    // SIGABORT and SIGCLD are accepted as stop and reload signals
+    let mut nodes = HashMap::new();
+    NodeServiceDescriptor::load_tree(
+        &mut nodes,
+        &default_service_name,
+        load_directoried.as_slice(),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(nodes.len(), 2);
+}
+
+#[tokio::test]
+async fn test_diamond_deps() {
+    let load_path = PathBuf::from("test_data/test_diamond");
+    assert!(load_path.exists());
+
+    let load_directoried = vec![load_path.clone()];
+
+    let default_service_name = String::from("default.service");
+
+    let mut nodes = HashMap::new();
+    NodeServiceDescriptor::load_tree(
+        &mut nodes,
+        &default_service_name,
+        load_directoried.as_slice(),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(nodes.len(), 4);
+    assert!(nodes.contains_key("common.service"));
+}
+
+#[tokio::test]
+async fn test_cycle_path() {
+    let load_path = PathBuf::from("test_data/test_cycle_path");
+    assert!(load_path.exists());
+
+    let load_directoried = vec![load_path.clone()];
+
+    let default_service_name = String::from("default.service");
+
+    let mut nodes = HashMap::new();
+    let load_res = NodeServiceDescriptor::load_tree(
+        &mut nodes,
+        &default_service_name,
+        load_directoried.as_slice(),
+    )
+    .await
+    .unwrap_err();
+
+    match load_res {
+        crate::errors::NodeLoadingError::CyclicDependency(cycle) => {
+            assert_eq!(cycle, "a.service -> b.service -> c.service -> a.service")
+        }
+        _ => panic!("wrong error type"),
+    }
+}
+
+#[tokio::test]
+async fn test_precedence() {
+    let high = PathBuf::from("test_data/test_precedence/high");
+    let low = PathBuf::from("test_data/test_precedence/low");
+    assert!(high.exists() && low.exists());
+
+    let load_directoried = vec![high.clone(), low.clone()];
+
+    let default_service_name = String::from("default.service");
+
+    let mut nodes = HashMap::new();
+    NodeServiceDescriptor::load_tree(
+        &mut nodes,
+        &default_service_name,
+        load_directoried.as_slice(),
+    )
+    .await
+    .unwrap();
+
+    let source = nodes
+        .get(&default_service_name)
+        .unwrap()
+        .source()
+        .cloned()
+        .unwrap();
+    assert_eq!(source.path(), &high.join(&default_service_name));
+    assert_eq!(source.shadowed(), &[low.join(&default_service_name)]);
+}
+
+#[tokio::test]
+async fn test_dropins() {
+    let high = PathBuf::from("test_data/test_dropins/high");
+    let low = PathBuf::from("test_data/test_dropins/low");
+    assert!(high.exists() && low.exists());
+
+    let load_directoried = vec![high.clone(), low.clone()];
+
+    let default_service_name = String::from("default.service");
+
+    let mut nodes = HashMap::new();
+    NodeServiceDescriptor::load_tree(
+        &mut nodes,
+        &default_service_name,
+        load_directoried.as_slice(),
+    )
+    .await
+    .unwrap();
+
+    let node = nodes.get(&default_service_name).unwrap();
+    assert_eq!(node.cmd(), "steam");
+    assert_eq!(node.args(), &["-e", "--steam"]);
+    assert_eq!(
+        node.environment(),
+        &HashMap::from([
+            (String::from("KEPT"), String::from("1")),
+            (String::from("REPLACED"), String::from("high")),
+            (String::from("ADDED"), String::from("1")),
+        ])
+    );
+
+    let source = node.source().unwrap();
+    assert_eq!(
+        source.dropins(),
+        &[
+            high.join("default.service.d/10-env.json"),
+            low.join("default.service.d/20-cmd.json")
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_templates() {
+    let load_path = PathBuf::from("test_data/test_templates");
+    assert!(load_path.exists());
+
+    let load_directoried = vec![load_path.clone()];
+
+    let default_service_name = String::from("default.service");
+
+    let mut nodes = HashMap::new();
+    NodeServiceDescriptor::load_tree(
+        &mut nodes,
+        &default_service_name,
+        load_directoried.as_slice(),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(nodes.len(), 3);
+
+    for instance in ["one", "two"] {
+        let name = format!("helper@{instance}.service");
+        let node = nodes.get(&name).unwrap();
+        assert_eq!(node.cmd(), "helper-helper");
+        assert_eq!(node.args(), &["--display", instance, "100%"]);
+        assert_eq!(node.environment().get("UNIT"), Some(&name));
+        assert_eq!(
+            node.source().unwrap().path(),
+            &load_path.join("helper@.service")
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_extends() {
+    let load_path = PathBuf::from("test_data/test_extends");
+    assert!(load_path.exists());
+
+    let load_directoried = vec![load_path.clone()];
+
+    let default_service_name = String::from("default.service");
+
+    let mut nodes = HashMap::new();
+    NodeServiceDescriptor::load_tree(
+        &mut nodes,
+        &default_service_name,
+        load_directoried.as_slice(),
+    )
+    .await
+    .unwrap();
+
+    let node = nodes.get(&default_service_name).unwrap();
+    assert_eq!(node.cmd(), "gamescope");
+    assert_eq!(node.args(), &["--base", "--middle"]);
+    assert_eq!(
+        node.environment(),
+        &HashMap::from([
+            (String::from("A"), String::from("base")),
+            (String::from("B"), String::from("middle")),
+            (String::from("C"), String::from("dropin")),
+        ])
+    );
+
+    let source = node.source().unwrap();
+    assert_eq!(
+        source.extends(),
+        &[load_path.join("base.json"), load_path.join("middle.json")]
+    );
+    assert_eq!(
+        source.dropins(),
+        &[load_path.join("base.json.d/10-env.json")]
+    );
+}
+
+#[tokio::test]
+async fn test_extends_cycle() {
+    let load_path = PathBuf::from("test_data/test_extends_cycle");
+    assert!(load_path.exists());
+
+    let load_directoried = vec![load_path.clone()];
+
+    let default_service_name = String::from("default.service");
+
+    let mut nodes = HashMap::new();
+    let load_res = NodeServiceDescriptor::load_tree(
+        &mut nodes,
+        &default_service_name,
+        load_directoried.as_slice(),
+    )
+    .await
+    .unwrap_err();
+
+    match load_res {
+        crate::errors::NodeLoadingError::CyclicExtends(cycle) => {
+            assert_eq!(cycle, "a.json -> b.json -> a.json")
+        }
+        _ => panic!("wrong error type"),
     }
 }
 
@@ -94,7 +353,7 @@ async fn test_restart() {
     .await
     .unwrap();
 
-    let manager = Arc::new(SessionManager::new(nodes));
+    let manager = Arc::new(SessionManager::new(nodes, load_directoried, None));
 
     let service = String::from("default.service");
 
@@ -106,8 +365,1660 @@ async fn test_restart() {
     res1.unwrap();
     res2.unwrap();
 
+    let history = manager.history(&service).await.unwrap();
+    assert_eq!(history.len(), 2);
+    assert!(matches!(
+        history[0].reason(),
+        SessionNodeStopReason::ManuallyRestarted
+    ));
+    assert!(matches!(
+        history[1].reason(),
+        SessionNodeStopReason::Completed(status) if status.success()
+    ));
+    assert!(history.iter().all(|run| run.usage().is_some()));
+
     std::fs::remove_file("f1").unwrap();
     std::fs::remove_file("f2").unwrap();
 
     assert!(!std::fs::exists("f3").unwrap())
 }
+
+#[tokio::test]
+async fn test_hooks() {
+    let load_path = PathBuf::from("test_data/test_hooks");
+    assert!(load_path.exists());
+
+    let load_directoried = vec![load_path.clone()];
+
+    let default_service_name = String::from("default.service");
+
+    let mut nodes = HashMap::new();
+    NodeServiceDescriptor::load_tree(
+        &mut nodes,
+        &default_service_name,
+        load_directoried.as_slice(),
+    )
+    .await
+    .unwrap();
+
+    let manager = Arc::new(SessionManager::new(nodes, load_directoried, None));
+
+    manager.run(&default_service_name).await.unwrap();
+
+    let history = manager.history(&default_service_name).await.unwrap();
+    assert_eq!(history.len(), 1);
+    assert!(matches!(
+        history[0].reason(),
+        SessionNodeStopReason::Completed(status) if status.success()
+    ));
+
+    assert!(!std::fs::exists("hook_pre").unwrap());
+    std::fs::remove_file("hook_post").unwrap();
+}
+
+#[tokio::test]
+async fn test_hooks_not_started() {
+    let load_path = PathBuf::from("test_data/test_hooks_waiting");
+    assert!(load_path.exists());
+
+    let load_directoried = vec![load_path.clone()];
+
+    let default_service_name = String::from("default.service");
+    let late_service_name = String::from("late.service");
+    let waiting_service_name = String::from("waiting.service");
+    let target_name = String::from("target_hooks.target");
+
+    let mut nodes = HashMap::new();
+    let errors = NodeServiceDescriptor::load_all(&mut nodes, load_directoried.as_slice()).await;
+    assert!(errors.is_empty());
+
+    let manager = Arc::new(SessionManager::new(nodes, load_directoried, None));
+
+    let (res1, res2) = join!(manager.run(&default_service_name), async {
+        // stopped while still waiting for the node it is ordered after
+        manager.start(&late_service_name).await.unwrap();
+        manager.start(&waiting_service_name).await.unwrap();
+        sleep(Duration::from_millis(300)).await;
+        assert!(!manager.is_running(&waiting_service_name).await.unwrap());
+
+        manager
+            .stop(&waiting_service_name, StopMode::Plain)
+            .await
+            .unwrap();
+        assert!(manager
+            .history(&waiting_service_name)
+            .await
+            .unwrap()
+            .is_empty());
+
+        // a target has no process of its own
+        manager.start(&target_name).await.unwrap();
+        sleep(Duration::from_millis(300)).await;
+        manager.stop(&target_name, StopMode::Plain).await.unwrap();
+
+        sleep(Duration::from_millis(300)).await;
+        assert!(!std::fs::exists("hook_waiting_post").unwrap());
+        assert!(!std::fs::exists("hook_target_post").unwrap());
+
+        manager
+            .stop(&late_service_name, StopMode::Plain)
+            .await
+            .unwrap();
+        manager.stop(&default_service_name, StopMode::Plain).await
+    });
+
+    res1.unwrap();
+    res2.unwrap();
+}
+
+#[tokio::test]
+async fn test_hooks_failure() {
+    let load_path = PathBuf::from("test_data/test_hooks_failure");
+    assert!(load_path.exists());
+
+    let load_directoried = vec![load_path.clone()];
+
+    let default_service_name = String::from("default.service");
+
+    let mut nodes = HashMap::new();
+    NodeServiceDescriptor::load_tree(
+        &mut nodes,
+        &default_service_name,
+        load_directoried.as_slice(),
+    )
+    .await
+    .unwrap();
+
+    let manager = Arc::new(SessionManager::new(nodes, load_directoried, None));
+
+    manager.run(&default_service_name).await.unwrap();
+
+    let history = manager.history(&default_service_name).await.unwrap();
+    assert!(history.is_empty());
+
+    assert!(!std::fs::exists("hook_never").unwrap());
+}
+
+#[tokio::test]
+async fn test_stop_command() {
+    let load_path = PathBuf::from("test_data/test_stop_command");
+    assert!(load_path.exists());
+
+    let load_directoried = vec![load_path.clone()];
+
+    let default_service_name = String::from("default.service");
+    let stubborn_service_name = String::from("stubborn.service");
+    let slow_start_service_name = String::from("slow_start.service");
+
+    let mut nodes = HashMap::new();
+    NodeServiceDescriptor::load_all(&mut nodes, load_directoried.as_slice()).await;
+
+    let manager = Arc::new(SessionManager::new(nodes, load_directoried, None));
+
+    let (res1, res2) = join!(manager.run(&default_service_name), async {
+        sleep(Duration::from_millis(500)).await;
+
+        assert!(matches!(
+            manager.reload(&default_service_name).await,
+            Err(SessionManagerError::ManualActionError(
+                ManualActionIssueError::ReloadNotSupported
+            ))
+        ));
+
+        // a stop issued while pre-start hooks run prevents the process from being spawned
+        manager.start(&slow_start_service_name).await.unwrap();
+        sleep(Duration::from_millis(200)).await;
+        timeout(
+            Duration::from_secs(3),
+            manager.stop(&slow_start_service_name, StopMode::Plain),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        sleep(Duration::from_millis(200)).await;
+        assert!(!manager.is_running(&slow_start_service_name).await.unwrap());
+        assert!(matches!(
+            manager.status(&slow_start_service_name).await.unwrap(),
+            SessionNodeStatus::Stopped {
+                reason: SessionNodeStopReason::ManuallyStopped,
+                ..
+            }
+        ));
+        assert!(manager
+            .history(&slow_start_service_name)
+            .await
+            .unwrap()
+            .is_empty());
+
+        // the last resort SIGKILL reaches the children of the node too
+        manager.start(&stubborn_service_name).await.unwrap();
+        sleep(Duration::from_millis(500)).await;
+        let child = std::fs::read_to_string("stubborn_child")
+            .unwrap()
+            .trim()
+            .parse::<i32>()
+            .unwrap();
+        manager
+            .stop(&stubborn_service_name, StopMode::Plain)
+            .await
+            .unwrap();
+        sleep(Duration::from_millis(500)).await;
+        let state = std::fs::read_to_string(format!("/proc/{child}/stat")).unwrap_or_default();
+        assert!(state.is_empty() || state.contains(") Z "));
+        std::fs::remove_file("stubborn_child").unwrap();
+
+        manager.stop(&default_service_name, StopMode::Plain).await
+    });
+
+    res1.unwrap();
+    res2.unwrap();
+
+    let history = manager.history(&default_service_name).await.unwrap();
+    assert_eq!(history.len(), 1);
+    assert!(matches!(
+        history[0].reason(),
+        SessionNodeStopReason::ManuallyStopped
+    ));
+
+    // the stop command got the pid without going through a shell
+    std::fs::remove_file("stop_cmd").unwrap();
+}
+
+#[tokio::test]
+async fn test_reload() {
+    let load_path = PathBuf::from("test_data/test_reload");
+    assert!(load_path.exists());
+
+    let load_directoried = vec![load_path.clone()];
+
+    let default_service_name = String::from("default.service");
+
+    let mut nodes = HashMap::new();
+    NodeServiceDescriptor::load_tree(
+        &mut nodes,
+        &default_service_name,
+        load_directoried.as_slice(),
+    )
+    .await
+    .unwrap();
+
+    let manager = Arc::new(SessionManager::new(nodes, load_directoried, None));
+
+    let (res1, res2) = join!(manager.run(&default_service_name), async {
+        sleep(Duration::from_millis(500)).await;
+        manager.reload(&default_service_name).await.unwrap();
+
+        sleep(Duration::from_millis(500)).await;
+        assert!(manager.is_running(&default_service_name).await.unwrap());
+
+        manager.stop(&default_service_name, StopMode::Plain).await
+    });
+
+    res1.unwrap();
+    res2.unwrap();
+
+    let history = manager.history(&default_service_name).await.unwrap();
+    assert_eq!(history.len(), 1);
+
+    std::fs::remove_file("reload_sig").unwrap();
+}
+
+#[tokio::test]
+async fn test_kill() {
+    let load_path = PathBuf::from("test_data/test_kill");
+    assert!(load_path.exists());
+
+    let load_directoried = vec![load_path.clone()];
+
+    let default_service_name = String::from("default.service");
+
+    let mut nodes = HashMap::new();
+    NodeServiceDescriptor::load_tree(
+        &mut nodes,
+        &default_service_name,
+        load_directoried.as_slice(),
+    )
+    .await
+    .unwrap();
+
+    let manager = Arc::new(SessionManager::new(nodes, load_directoried, None));
+
+    let (res1, res2) = join!(manager.run(&default_service_name), async {
+        sleep(Duration::from_millis(500)).await;
+        manager
+            .kill(&default_service_name, Signal::SIGUSR1, KillWhom::Group)
+            .await
+            .unwrap();
+
+        sleep(Duration::from_millis(500)).await;
+        assert!(std::fs::exists("kill_sig").unwrap());
+        assert!(manager.is_running(&default_service_name).await.unwrap());
+
+        manager.stop(&default_service_name, StopMode::Plain).await
+    });
+
+    res1.unwrap();
+    res2.unwrap();
+
+    let history = manager.history(&default_service_name).await.unwrap();
+    assert_eq!(history.len(), 1);
+    assert!(matches!(
+        history[0].reason(),
+        SessionNodeStopReason::ManuallyStopped
+    ));
+
+    std::fs::remove_file("kill_sig").unwrap();
+}
+
+#[tokio::test]
+async fn test_load_all() {
+    let load_path = PathBuf::from("test_data/test_load_all");
+    assert!(load_path.exists());
+
+    let load_directoried = vec![load_path.clone()];
+
+    let default_service_name = String::from("default.service");
+    let extra_service_name = String::from("extra.service");
+    let instance_name = String::from("worker@one.service");
+
+    let mut nodes = HashMap::new();
+    NodeServiceDescriptor::load_tree(
+        &mut nodes,
+        &default_service_name,
+        load_directoried.as_slice(),
+    )
+    .await
+    .unwrap();
+
+    let errors = NodeServiceDescriptor::load_all(&mut nodes, load_directoried.as_slice()).await;
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].0, "broken.service");
+    assert!(matches!(errors[0].1, NodeLoadingError::JSONError(_)));
+
+    assert!(nodes.contains_key(&extra_service_name));
+    assert!(!nodes.contains_key("worker@.service"));
+
+    let manager = Arc::new(SessionManager::new(nodes, load_directoried, None));
+
+    let (res1, res2) = join!(manager.run(&default_service_name), async {
+        sleep(Duration::from_millis(500)).await;
+
+        // services outside of the default target closure are not started at boot
+        assert!(!manager.is_running(&extra_service_name).await.unwrap());
+
+        assert!(manager.start(&extra_service_name).await.unwrap().started());
+        assert!(manager.start(&instance_name).await.unwrap().started());
+
+        sleep(Duration::from_millis(500)).await;
+        assert!(manager.is_running(&extra_service_name).await.unwrap());
+        assert!(manager.is_running(&instance_name).await.unwrap());
+
+        // already supervised services are not started twice
+        assert!(!manager.start(&extra_service_name).await.unwrap().started());
+
+        manager
+            .stop(&extra_service_name, StopMode::Plain)
+            .await
+            .unwrap();
+        manager.stop(&instance_name, StopMode::Plain).await.unwrap();
+
+        sleep(Duration::from_millis(500)).await;
+        assert!(!manager.is_running(&extra_service_name).await.unwrap());
+        assert!(!manager.is_running(&instance_name).await.unwrap());
+
+        manager.start(&String::from("missing.service")).await
+    });
+
+    res1.unwrap();
+    assert!(matches!(res2, Err(SessionManagerError::NotFound(_))));
+
+    let history = manager.history(&extra_service_name).await.unwrap();
+    assert_eq!(history.len(), 1);
+    assert!(matches!(
+        history[0].reason(),
+        SessionNodeStopReason::ManuallyStopped
+    ));
+}
+
+#[tokio::test]
+async fn test_requires() {
+    let load_path = PathBuf::from("test_data/test_requires");
+    assert!(load_path.exists());
+
+    let load_directoried = vec![load_path.clone()];
+
+    let default_service_name = String::from("default.service");
+    let startup_service_name = String::from("startup.service");
+
+    let mut nodes = HashMap::new();
+    for name in [&default_service_name, &startup_service_name] {
+        NodeServiceDescriptor::load_tree(&mut nodes, name, load_directoried.as_slice())
+            .await
+            .unwrap();
+    }
+
+    let manager = Arc::new(SessionManager::new(nodes, load_directoried, None));
+
+    // a required dependency failing before the node starts prevents it from starting
+    let outcome = timeout(Duration::from_secs(5), manager.run(&startup_service_name))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(outcome.end(), SessionEnd::Failed);
+    assert!(matches!(outcome.main(), RunResult::NeverRun));
+    assert_eq!(outcome.failed(), ["startup.service", "unspawnable.service"]);
+    assert!(manager
+        .history(&startup_service_name)
+        .await
+        .unwrap()
+        .is_empty());
+
+    // the node is started after setup.service, ignores the failure of the wanted
+    // flaky.service and is stopped when the required dies.service fails
+    timeout(Duration::from_secs(10), manager.run(&default_service_name))
+        .await
+        .unwrap()
+        .unwrap();
+
+    let history = manager.history(&default_service_name).await.unwrap();
+    assert_eq!(history.len(), 1);
+    assert!(matches!(
+        history[0].reason(),
+        SessionNodeStopReason::DependencyFailed(culprit) if culprit == "dies.service"
+    ));
+
+    std::fs::remove_file("requires_order").unwrap();
+}
+
+#[tokio::test]
+async fn test_pending_start() {
+    let load_path = PathBuf::from("test_data/test_pending_start");
+    assert!(load_path.exists());
+
+    let load_directoried = vec![load_path.clone()];
+
+    let default_service_name = String::from("default.service");
+    let early_service_name = String::from("early.service");
+    let late_service_name = String::from("late.service");
+    let blocker_service_name = String::from("blocker.service");
+
+    let mut nodes = HashMap::new();
+    let errors = NodeServiceDescriptor::load_all(&mut nodes, load_directoried.as_slice()).await;
+    assert!(errors.is_empty());
+
+    let manager = Arc::new(SessionManager::new(nodes, load_directoried, None));
+    manager.start(&blocker_service_name).await.unwrap();
+    sleep(Duration::from_millis(300)).await;
+    assert!(manager.is_running(&blocker_service_name).await.unwrap());
+
+    let (res1, res2) = join!(manager.run(&default_service_name), async {
+        // early is started first, but waits for late that is started in the same run
+        // once the conflicting blocker has stopped
+        sleep(Duration::from_millis(300)).await;
+        assert!(!manager.is_running(&late_service_name).await.unwrap());
+        assert!(!manager.is_running(&early_service_name).await.unwrap());
+
+        sleep(Duration::from_millis(1200)).await;
+        assert!(!manager.is_running(&blocker_service_name).await.unwrap());
+        assert!(manager.is_running(&late_service_name).await.unwrap());
+        assert!(manager.is_running(&early_service_name).await.unwrap());
+
+        manager
+            .stop(&early_service_name, StopMode::Plain)
+            .await
+            .unwrap();
+        manager
+            .stop(&late_service_name, StopMode::Plain)
+            .await
+            .unwrap();
+        manager.stop(&default_service_name, StopMode::Plain).await
+    });
+
+    res1.unwrap();
+    res2.unwrap();
+}
+
+#[tokio::test]
+async fn test_conflicts() {
+    let load_path = PathBuf::from("test_data/test_conflicts");
+    assert!(load_path.exists());
+
+    let load_directoried = vec![load_path.clone()];
+
+    let default_service_name = String::from("default.service");
+    let game_service_name = String::from("game.service");
+    let desktop_service_name = String::from("desktop.service");
+    let launcher_service_name = String::from("launcher.service");
+
+    let mut nodes = HashMap::new();
+    NodeServiceDescriptor::load_tree(
+        &mut nodes,
+        &default_service_name,
+        load_directoried.as_slice(),
+    )
+    .await
+    .unwrap();
+
+    let errors = NodeServiceDescriptor::load_all(&mut nodes, load_directoried.as_slice()).await;
+    assert!(errors.is_empty());
+
+    let manager = Arc::new(SessionManager::new(nodes, load_directoried, None));
+
+    let (res1, res2) = join!(manager.run(&default_service_name), async {
+        let outcome = manager.start(&desktop_service_name).await.unwrap();
+        assert!(outcome.started());
+        assert!(outcome.stopped().is_empty());
+
+        sleep(Duration::from_millis(300)).await;
+        assert!(manager.is_running(&desktop_service_name).await.unwrap());
+
+        // the conflicting node has fully exited once start returns
+        let outcome = manager.start(&game_service_name).await.unwrap();
+        assert!(outcome.started());
+        assert_eq!(outcome.stopped(), [desktop_service_name.as_str()]);
+        assert!(!manager.is_running(&desktop_service_name).await.unwrap());
+
+        sleep(Duration::from_millis(300)).await;
+        assert!(manager.is_running(&game_service_name).await.unwrap());
+
+        // conflicts are mutual
+        let outcome = manager.start(&desktop_service_name).await.unwrap();
+        assert_eq!(outcome.stopped(), [game_service_name.as_str()]);
+        assert!(!manager.is_running(&game_service_name).await.unwrap());
+
+        // restarting a stopped node starts it like start does
+        sleep(Duration::from_millis(300)).await;
+        assert!(manager.is_running(&desktop_service_name).await.unwrap());
+        manager.restart(&game_service_name).await.unwrap();
+        assert!(!manager.is_running(&desktop_service_name).await.unwrap());
+        sleep(Duration::from_millis(300)).await;
+        assert!(manager.is_running(&game_service_name).await.unwrap());
+
+        // so does starting a failure handler
+        manager.start(&launcher_service_name).await.unwrap();
+        sleep(Duration::from_millis(500)).await;
+        assert!(!manager.is_running(&game_service_name).await.unwrap());
+        assert!(manager.is_running(&desktop_service_name).await.unwrap());
+
+        manager
+            .stop(&desktop_service_name, StopMode::Plain)
+            .await
+            .unwrap();
+        manager.stop(&default_service_name, StopMode::Plain).await
+    });
+
+    res1.unwrap();
+    res2.unwrap();
+}
+
+#[tokio::test]
+async fn test_binds() {
+    let load_path = PathBuf::from("test_data/test_binds");
+    assert!(load_path.exists());
+
+    let load_directoried = vec![load_path.clone()];
+
+    let default_service_name = String::from("default.service");
+    let compositor_service_name = String::from("compositor.service");
+    let overlay_service_name = String::from("overlay.service");
+    let remapper_service_name = String::from("remapper.service");
+
+    let mut nodes = HashMap::new();
+    NodeServiceDescriptor::load_tree(
+        &mut nodes,
+        &default_service_name,
+        load_directoried.as_slice(),
+    )
+    .await
+    .unwrap();
+
+    let manager = Arc::new(SessionManager::new(nodes, load_directoried, None));
+
+    let (res1, res2) = join!(manager.run(&default_service_name), async {
+        sleep(Duration::from_millis(500)).await;
+        for name in [
+            &compositor_service_name,
+            &overlay_service_name,
+            &remapper_service_name,
+        ] {
+            assert!(manager.is_running(name).await.unwrap());
+        }
+
+        // restarting the compositor restarts both helpers
+        manager.restart(&compositor_service_name).await.unwrap();
+        sleep(Duration::from_millis(1000)).await;
+        for name in [&overlay_service_name, &remapper_service_name] {
+            assert!(manager.is_running(name).await.unwrap());
+
+            let history = manager.history(name).await.unwrap();
+            assert_eq!(history.len(), 1);
+            assert!(matches!(
+                history[0].reason(),
+                SessionNodeStopReason::ManuallyRestarted
+            ));
+        }
+
+        // the compositor crashing for good stops only the bound helper
+        manager
+            .kill(&compositor_service_name, Signal::SIGKILL, KillWhom::Main)
+            .await
+            .unwrap();
+        sleep(Duration::from_millis(1000)).await;
+        assert!(!manager.is_running(&compositor_service_name).await.unwrap());
+        assert!(!manager.is_running(&overlay_service_name).await.unwrap());
+        assert!(manager.is_running(&remapper_service_name).await.unwrap());
+
+        manager.stop(&remapper_service_name, StopMode::Plain).await
+    });
+
+    res1.unwrap();
+    res2.unwrap();
+}
+
+#[tokio::test]
+async fn test_targets() {
+    let load_path = PathBuf::from("test_data/test_targets");
+    assert!(load_path.exists());
+
+    let load_directoried = vec![load_path.clone()];
+
+    let default_service_name = String::from("default.service");
+    let session_service_name = String::from("session.service");
+
+    let mut nodes = HashMap::new();
+    match NodeServiceDescriptor::load_tree(
+        &mut nodes,
+        &String::from("invalid.service"),
+        load_directoried.as_slice(),
+    )
+    .await
+    {
+        Err(NodeLoadingError::InvalidCommand(name)) => assert_eq!(name, "invalid.service"),
+        _ => panic!("a target with a command must not load"),
+    }
+
+    NodeServiceDescriptor::load_tree(
+        &mut nodes,
+        &default_service_name,
+        load_directoried.as_slice(),
+    )
+    .await
+    .unwrap();
+
+    let manager = Arc::new(SessionManager::new(nodes, load_directoried, None));
+
+    let (res1, res2) = join!(manager.run(&default_service_name), async {
+        assert_eq!(
+            manager.kind(&default_service_name).await.unwrap(),
+            SessionNodeType::Target
+        );
+
+        // the target is reached only once the oneshot has completed
+        sleep(Duration::from_millis(500)).await;
+        assert!(manager.is_running(&session_service_name).await.unwrap());
+        assert!(!manager.is_running(&default_service_name).await.unwrap());
+
+        sleep(Duration::from_millis(1000)).await;
+        assert!(manager.is_running(&default_service_name).await.unwrap());
+
+        // stopping the target ends the session and stops what is part of it
+        manager.stop(&default_service_name, StopMode::Plain).await
+    });
+
+    res1.unwrap();
+    res2.unwrap();
+
+    sleep(Duration::from_millis(500)).await;
+    assert!(!manager.is_running(&session_service_name).await.unwrap());
+    assert!(manager
+        .history(&default_service_name)
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+#[tokio::test]
+async fn test_isolate() {
+    let load_path = PathBuf::from("test_data/test_isolate");
+    assert!(load_path.exists());
+
+    let load_directoried = vec![load_path.clone()];
+
+    let default_service_name = String::from("default.service");
+    let desktop_service_name = String::from("desktop.service");
+    let gaming_target_name = String::from("gaming.target");
+    let game_service_name = String::from("game.service");
+
+    let mut nodes = HashMap::new();
+    NodeServiceDescriptor::load_tree(
+        &mut nodes,
+        &default_service_name,
+        load_directoried.as_slice(),
+    )
+    .await
+    .unwrap();
+
+    // standalone targets are indexed together with services
+    let errors = NodeServiceDescriptor::load_all(&mut nodes, load_directoried.as_slice()).await;
+    assert!(errors.is_empty());
+    assert!(nodes.contains_key(&gaming_target_name));
+
+    let manager = Arc::new(SessionManager::new(nodes, load_directoried, None));
+
+    let (res1, res2) = join!(manager.run(&default_service_name), async {
+        sleep(Duration::from_millis(500)).await;
+        assert!(manager.is_running(&desktop_service_name).await.unwrap());
+
+        // a target that cannot be found leaves the session untouched
+        assert!(matches!(
+            manager.isolate(&String::from("missing.target"), None).await,
+            Err(SessionManagerError::NotFound(_))
+        ));
+        assert!(manager.is_running(&desktop_service_name).await.unwrap());
+
+        // neither does a target conflicting with the main node
+        assert!(matches!(
+            manager
+                .isolate(&String::from("exclusive.target"), None)
+                .await,
+            Err(SessionManagerError::ConflictsWithMain(_, _))
+        ));
+        assert!(manager.is_running(&desktop_service_name).await.unwrap());
+
+        let (progress, mut steps) = tokio::sync::mpsc::unbounded_channel();
+        let outcome = manager
+            .isolate(&gaming_target_name, Some(progress))
+            .await
+            .unwrap();
+        assert_eq!(outcome.stopped(), [desktop_service_name.as_str()]);
+        assert_eq!(
+            outcome.started(),
+            [game_service_name.as_str(), gaming_target_name.as_str()]
+        );
+
+        let mut reported = vec![];
+        while let Some(step) = steps.recv().await {
+            reported.push(step);
+        }
+        assert_eq!(
+            reported,
+            [
+                IsolateStep::Stopping(desktop_service_name.clone()),
+                IsolateStep::Stopped(desktop_service_name.clone()),
+                IsolateStep::Starting(game_service_name.clone()),
+                IsolateStep::Starting(gaming_target_name.clone()),
+            ]
+        );
+
+        sleep(Duration::from_millis(500)).await;
+        assert!(!manager.is_running(&desktop_service_name).await.unwrap());
+        assert!(manager.is_running(&game_service_name).await.unwrap());
+        assert!(manager.is_running(&gaming_target_name).await.unwrap());
+
+        // the main node is never stopped: isolating to it switches back
+        let (progress, mut steps) = tokio::sync::mpsc::unbounded_channel();
+        let outcome = manager
+            .isolate(&default_service_name, Some(progress))
+            .await
+            .unwrap();
+        assert_eq!(
+            outcome.stopped(),
+            [game_service_name.as_str(), gaming_target_name.as_str()]
+        );
+        assert_eq!(outcome.started(), [desktop_service_name.as_str()]);
+
+        // the target wanting the game is stopped first
+        let mut reported = vec![];
+        while let Some(step) = steps.recv().await {
+            reported.push(step);
+        }
+        assert_eq!(
+            reported,
+            [
+                IsolateStep::Stopping(gaming_target_name.clone()),
+                IsolateStep::Stopped(gaming_target_name.clone()),
+                IsolateStep::Stopping(game_service_name.clone()),
+                IsolateStep::Stopped(game_service_name.clone()),
+                IsolateStep::Starting(desktop_service_name.clone()),
+            ]
+        );
+
+        sleep(Duration::from_millis(500)).await;
+        assert!(manager.is_running(&desktop_service_name).await.unwrap());
+        assert!(!manager.is_running(&game_service_name).await.unwrap());
+
+        manager
+            .stop(&desktop_service_name, StopMode::Plain)
+            .await
+            .unwrap();
+        manager.stop(&default_service_name, StopMode::Plain).await
+    });
+
+    res1.unwrap();
+    res2.unwrap();
+}
+
+#[tokio::test]
+async fn test_jobs() {
+    let load_path = PathBuf::from("test_data/test_jobs");
+    assert!(load_path.exists());
+
+    let load_directoried = vec![load_path.clone()];
+
+    let default_service_name = String::from("default.service");
+    let slow_service_name = String::from("slow.service");
+    let other_service_name = String::from("other.service");
+    let client_service_name = String::from("client.service");
+
+    let mut nodes = HashMap::new();
+    NodeServiceDescriptor::load_all(&mut nodes, load_directoried.as_slice()).await;
+
+    let manager = Arc::new(SessionManager::new(nodes, load_directoried, None));
+
+    let (res1, res2) = join!(manager.run(&default_service_name), async {
+        manager.start(&slow_service_name).await.unwrap();
+        manager.start(&other_service_name).await.unwrap();
+        sleep(Duration::from_millis(300)).await;
+
+        // slow.service ignores SIGTERM: stopping it keeps the queue busy
+        let stop_ids = manager
+            .enqueue(&slow_service_name, JobKind::Stop)
+            .await
+            .unwrap();
+        let dispatcher = manager.clone();
+        let dispatching = tokio::spawn(async move { dispatcher.dispatch().await });
+
+        assert!(matches!(
+            manager.enqueue(&slow_service_name, JobKind::Start).await,
+            Err(SessionManagerError::JobConflict(_))
+        ));
+
+        // compatible jobs are merged into the waiting one
+        let restart_ids = manager
+            .enqueue(&other_service_name, JobKind::Restart)
+            .await
+            .unwrap();
+        let start_ids = manager
+            .enqueue(&other_service_name, JobKind::Start)
+            .await
+            .unwrap();
+        assert_eq!(restart_ids, start_ids);
+        assert!(matches!(
+            manager.enqueue(&other_service_name, JobKind::Stop).await,
+            Err(SessionManagerError::JobConflict(_))
+        ));
+
+        sleep(Duration::from_millis(200)).await;
+        assert!(matches!(
+            manager.cancel_job(stop_ids[0]).await,
+            Err(SessionManagerError::JobNotCancellable(_))
+        ));
+        manager.cancel_job(restart_ids[0]).await.unwrap();
+        assert_eq!(
+            *manager.job(restart_ids[0]).unwrap().state(),
+            JobState::Cancelled
+        );
+
+        let stop_job = manager.wait_job(stop_ids[0]).await.unwrap();
+        assert_eq!(*stop_job.state(), JobState::Done);
+        assert!(!manager.is_running(&slow_service_name).await.unwrap());
+        dispatching.await.unwrap();
+
+        // the cancelled restart has never been run
+        assert!(manager
+            .history(&other_service_name)
+            .await
+            .unwrap()
+            .is_empty());
+
+        // queued stops and restarts are refused while running nodes require their target
+        manager.start(&client_service_name).await.unwrap();
+        sleep(Duration::from_millis(300)).await;
+        assert!(matches!(
+            manager.enqueue(&other_service_name, JobKind::Stop).await,
+            Err(SessionManagerError::RequiredBy(_, _))
+        ));
+        assert!(matches!(
+            manager.enqueue(&other_service_name, JobKind::Restart).await,
+            Err(SessionManagerError::RequiredBy(_, _))
+        ));
+        assert!(manager.is_running(&other_service_name).await.unwrap());
+
+        manager
+            .stop(&client_service_name, StopMode::Plain)
+            .await
+            .unwrap();
+        manager.stop(&other_service_name, StopMode::Plain).await
+    });
+
+    res1.unwrap();
+    res2.unwrap();
+}
+
+#[tokio::test]
+async fn test_dependency_failure() {
+    let load_path = PathBuf::from("test_data/test_dependency_failure");
+    assert!(load_path.exists());
+
+    let load_directoried = vec![load_path.clone()];
+
+    let default_service_name = String::from("default.service");
+    let backend_service_name = String::from("backend.service");
+    let anyway_service_name = String::from("anyway.service");
+    let failing_service_name = String::from("failing.service");
+    let waiting_service_name = String::from("waiting.service");
+
+    let mut nodes = HashMap::new();
+    match NodeServiceDescriptor::load_tree(
+        &mut nodes,
+        &String::from("invalid.service"),
+        load_directoried.as_slice(),
+    )
+    .await
+    {
+        Err(NodeLoadingError::InvalidPolicy(policy)) => assert_eq!(policy, "retry"),
+        _ => panic!("invalid.service has an unknown policy"),
+    }
+
+    NodeServiceDescriptor::load_tree(
+        &mut nodes,
+        &default_service_name,
+        load_directoried.as_slice(),
+    )
+    .await
+    .unwrap();
+
+    let manager = Arc::new(SessionManager::new(nodes, load_directoried, None));
+
+    let (res1, res2) = join!(manager.run(&default_service_name), async {
+        sleep(Duration::from_millis(500)).await;
+        for name in [
+            &anyway_service_name,
+            &failing_service_name,
+            &waiting_service_name,
+        ] {
+            assert!(manager.is_running(name).await.unwrap());
+        }
+
+        manager
+            .kill(&backend_service_name, Signal::SIGKILL, KillWhom::Main)
+            .await
+            .unwrap();
+        sleep(Duration::from_millis(1000)).await;
+
+        assert!(manager.is_running(&anyway_service_name).await.unwrap());
+        assert!(!manager.is_running(&failing_service_name).await.unwrap());
+        assert!(matches!(
+            manager.status(&failing_service_name).await.unwrap(),
+            SessionNodeStatus::Stopped {
+                reason: SessionNodeStopReason::DependencyFailed(culprit),
+                ..
+            } if culprit == backend_service_name
+        ));
+        assert!(!manager.is_running(&waiting_service_name).await.unwrap());
+        assert!(matches!(
+            manager.status(&waiting_service_name).await.unwrap(),
+            SessionNodeStatus::Waiting { culprit } if culprit == backend_service_name
+        ));
+
+        // restarting the dependency resumes only the waiting node
+        manager.restart(&backend_service_name).await.unwrap();
+        sleep(Duration::from_millis(1000)).await;
+        assert!(manager.is_running(&waiting_service_name).await.unwrap());
+        assert!(!manager.is_running(&failing_service_name).await.unwrap());
+
+        for name in [
+            &anyway_service_name,
+            &waiting_service_name,
+            &backend_service_name,
+        ] {
+            manager.stop(name, StopMode::Plain).await.unwrap();
+        }
+
+        manager.stop(&default_service_name, StopMode::Plain).await
+    });
+
+    res1.unwrap();
+    res2.unwrap();
+}
+
+#[tokio::test]
+async fn test_cascade() {
+    let load_path = PathBuf::from("test_data/test_cascade");
+    assert!(load_path.exists());
+
+    let load_directoried = vec![load_path.clone()];
+
+    let default_service_name = String::from("default.service");
+    let database_service_name = String::from("database.service");
+    let cache_service_name = String::from("cache.service");
+    let app_service_name = String::from("app.service");
+    let monitor_service_name = String::from("monitor.service");
+
+    let mut nodes = HashMap::new();
+    NodeServiceDescriptor::load_tree(
+        &mut nodes,
+        &default_service_name,
+        load_directoried.as_slice(),
+    )
+    .await
+    .unwrap();
+
+    let errors = NodeServiceDescriptor::load_all(&mut nodes, load_directoried.as_slice()).await;
+    assert!(errors.is_empty());
+
+    let manager = Arc::new(SessionManager::new(nodes, load_directoried, None));
+
+    let (res1, res2) = join!(manager.run(&default_service_name), async {
+        manager.start(&app_service_name).await.unwrap();
+        manager.start(&monitor_service_name).await.unwrap();
+        sleep(Duration::from_millis(500)).await;
+
+        // the database is required by the running cache
+        match manager.stop(&database_service_name, StopMode::Plain).await {
+            Err(SessionManagerError::RequiredBy(name, required_by)) => {
+                assert_eq!(name, database_service_name);
+                assert_eq!(required_by, cache_service_name);
+            }
+            _ => panic!("database.service is still required"),
+        }
+        assert!(manager.is_running(&database_service_name).await.unwrap());
+
+        // dependents are stopped first, in reverse dependency order
+        let stopped = manager
+            .stop(&database_service_name, StopMode::Cascade)
+            .await
+            .unwrap();
+        assert_eq!(stopped.len(), 4);
+        let position = |name: &String| stopped.iter().position(|node| node == name).unwrap();
+        assert!(position(&app_service_name) < position(&cache_service_name));
+        assert!(position(&cache_service_name) < position(&database_service_name));
+        assert!(position(&monitor_service_name) < position(&database_service_name));
+        for name in [
+            &database_service_name,
+            &cache_service_name,
+            &app_service_name,
+            &monitor_service_name,
+        ] {
+            assert!(!manager.is_running(name).await.unwrap());
+        }
+
+        // a forced stop leaves dependents running
+        manager.start(&cache_service_name).await.unwrap();
+        sleep(Duration::from_millis(500)).await;
+        let stopped = manager
+            .stop(&database_service_name, StopMode::Force)
+            .await
+            .unwrap();
+        assert_eq!(stopped, vec![database_service_name.clone()]);
+        assert!(manager.is_running(&cache_service_name).await.unwrap());
+
+        manager
+            .stop(&cache_service_name, StopMode::Plain)
+            .await
+            .unwrap();
+        manager.stop(&default_service_name, StopMode::Plain).await
+    });
+
+    assert_eq!(res1.unwrap().end(), SessionEnd::Stopped);
+    res2.unwrap();
+}
+
+#[tokio::test]
+async fn test_on_failure() {
+    let load_path = PathBuf::from("test_data/test_on_failure");
+    assert!(load_path.exists());
+
+    let load_directoried = vec![load_path.clone()];
+
+    let default_service_name = String::from("default.service");
+    let crashing_service_name = String::from("crashing.service");
+    let quitter_service_name = String::from("quitter.service");
+    let report_service_name = String::from("report.service");
+    let retrying_service_name = String::from("retrying.service");
+
+    let mut nodes = HashMap::new();
+    NodeServiceDescriptor::load_tree(
+        &mut nodes,
+        &default_service_name,
+        load_directoried.as_slice(),
+    )
+    .await
+    .unwrap();
+
+    let errors = NodeServiceDescriptor::load_all(&mut nodes, load_directoried.as_slice()).await;
+    assert!(errors.is_empty());
+
+    let manager = Arc::new(SessionManager::new(nodes, load_directoried, None));
+
+    let (res1, res2) = join!(manager.run(&default_service_name), async {
+        // handlers are started once the node has failed for good
+        manager.start(&crashing_service_name).await.unwrap();
+        sleep(Duration::from_millis(1000)).await;
+        assert_eq!(
+            manager.history(&crashing_service_name).await.unwrap().len(),
+            2
+        );
+        assert_eq!(
+            manager.history(&report_service_name).await.unwrap().len(),
+            1
+        );
+        assert_eq!(
+            std::fs::read_to_string("on_failure_report").unwrap(),
+            "crashing.service: completed (exit status: 3)\n"
+        );
+
+        // the failure is described only to the run handling it
+        manager.start(&report_service_name).await.unwrap();
+        sleep(Duration::from_millis(300)).await;
+        assert_eq!(
+            std::fs::read_to_string("on_failure_report").unwrap(),
+            ": \n"
+        );
+
+        // a node can handle its own failure
+        manager.start(&retrying_service_name).await.unwrap();
+        sleep(Duration::from_millis(1000)).await;
+        assert_eq!(
+            manager.history(&retrying_service_name).await.unwrap().len(),
+            2
+        );
+        std::fs::remove_file("retrying_marker").unwrap();
+
+        // a requested stop is not a failure
+        manager.start(&quitter_service_name).await.unwrap();
+        sleep(Duration::from_millis(300)).await;
+        manager
+            .stop(&quitter_service_name, StopMode::Plain)
+            .await
+            .unwrap();
+        sleep(Duration::from_millis(300)).await;
+        assert_eq!(
+            manager.history(&report_service_name).await.unwrap().len(),
+            2
+        );
+
+        manager.stop(&default_service_name, StopMode::Plain).await
+    });
+
+    res1.unwrap();
+    res2.unwrap();
+
+    std::fs::remove_file("on_failure_report").unwrap();
+}
+
+#[tokio::test]
+async fn test_rescue() {
+    let load_path = PathBuf::from("test_data/test_rescue");
+    assert!(load_path.exists());
+
+    let load_directoried = vec![load_path.clone()];
+
+    let default_service_name = String::from("default.service");
+    let flaky_service_name = String::from("flaky.service");
+    let other_service_name = String::from("other.service");
+    let rescue_service_name = String::from("rescue.service");
+    let crasher_service_name = String::from("crasher.service");
+    let handler_service_name = String::from("handler.service");
+
+    // the main node failing switches the session to the rescue target
+    let mut nodes = HashMap::new();
+    NodeServiceDescriptor::load_tree(
+        &mut nodes,
+        &default_service_name,
+        load_directoried.as_slice(),
+    )
+    .await
+    .unwrap();
+
+    let manager = Arc::new(SessionManager::new(
+        nodes,
+        load_directoried.clone(),
+        Some(rescue_service_name.clone()),
+    ));
+
+    let (res1, res2) = join!(manager.run(&default_service_name), async {
+        sleep(Duration::from_millis(200)).await;
+        assert!(manager.rescued().await.is_none());
+        assert!(manager.is_running(&other_service_name).await.unwrap());
+
+        sleep(Duration::from_millis(1000)).await;
+        assert!(manager.is_running(&rescue_service_name).await.unwrap());
+        assert!(!manager.is_running(&other_service_name).await.unwrap());
+
+        let decision = manager.rescued().await.unwrap();
+        assert_eq!(decision.target(), rescue_service_name);
+        assert!(decision.cause().starts_with("default.service has failed"));
+
+        // failures are still handled while the rescue target is running
+        manager.start(&crasher_service_name).await.unwrap();
+        sleep(Duration::from_millis(500)).await;
+        assert!(manager.is_running(&handler_service_name).await.unwrap());
+        manager
+            .stop(&handler_service_name, StopMode::Plain)
+            .await
+            .unwrap();
+
+        manager.stop(&rescue_service_name, StopMode::Plain).await
+    });
+
+    assert_eq!(res1.unwrap().end(), SessionEnd::Rescued);
+    res2.unwrap();
+
+    // stopping the main node while it is waiting to be restarted also does
+    let mut nodes = HashMap::new();
+    NodeServiceDescriptor::load_tree(&mut nodes, &flaky_service_name, load_directoried.as_slice())
+        .await
+        .unwrap();
+
+    let manager = Arc::new(SessionManager::new(
+        nodes,
+        load_directoried,
+        Some(rescue_service_name.clone()),
+    ));
+
+    let (res1, res2) = join!(manager.run(&flaky_service_name), async {
+        sleep(Duration::from_millis(300)).await;
+        manager
+            .stop(&flaky_service_name, StopMode::Plain)
+            .await
+            .unwrap();
+
+        sleep(Duration::from_millis(300)).await;
+        assert!(manager.is_running(&rescue_service_name).await.unwrap());
+
+        let decision = manager.rescued().await.unwrap();
+        assert!(decision
+            .cause()
+            .starts_with("flaky.service has been stopped after failing"));
+
+        manager.stop(&rescue_service_name, StopMode::Plain).await
+    });
+
+    res1.unwrap();
+    res2.unwrap();
+}
+
+#[tokio::test]
+async fn test_shutdown() {
+    let load_path = PathBuf::from("test_data/test_shutdown");
+    assert!(load_path.exists());
+
+    // descriptors added while the session is running are loaded on request
+    let added_path = std::env::temp_dir().join("sessionrunner_test_shutdown");
+    let _ = std::fs::remove_dir_all(&added_path);
+    std::fs::create_dir(&added_path).unwrap();
+
+    let load_directoried = vec![load_path.clone(), added_path.clone()];
+
+    let default_service_name = String::from("default.service");
+    let app_service_name = String::from("app.service");
+    let base_service_name = String::from("base.service");
+    let stubborn_service_name = String::from("stubborn.service");
+    let added_service_name = String::from("added.service");
+
+    let mut nodes = HashMap::new();
+    NodeServiceDescriptor::load_tree(
+        &mut nodes,
+        &default_service_name,
+        load_directoried.as_slice(),
+    )
+    .await
+    .unwrap();
+
+    let manager = Arc::new(SessionManager::new(nodes, load_directoried, None));
+
+    let (res1, _) = join!(manager.run(&default_service_name), async {
+        sleep(Duration::from_millis(500)).await;
+
+        let dump = manager.dump().await;
+        assert_eq!(dump.len(), 4);
+        assert!(dump[0].starts_with("app.service (service): running (pid"));
+
+        std::fs::copy(
+            load_path.join(&base_service_name),
+            added_path.join(&added_service_name),
+        )
+        .unwrap();
+        let outcome = manager.daemon_reload().await.unwrap();
+        assert_eq!(outcome.added(), [added_service_name.as_str()]);
+        assert_eq!(
+            manager.kind(&added_service_name).await.unwrap(),
+            SessionNodeType::Service
+        );
+
+        // nodes ignoring the stop request are killed once the timeout expires
+        timeout(
+            Duration::from_secs(5),
+            manager.shutdown(Duration::from_secs(1)),
+        )
+        .await
+        .unwrap();
+
+        for name in [
+            &default_service_name,
+            &app_service_name,
+            &base_service_name,
+            &stubborn_service_name,
+        ] {
+            assert!(!manager.is_running(name).await.unwrap());
+        }
+
+        // dependents are stopped before their dependencies
+        let app_history = manager.history(&app_service_name).await.unwrap();
+        let base_history = manager.history(&base_service_name).await.unwrap();
+        assert!(app_history[0].stopped() <= base_history[0].stopped());
+    });
+
+    assert_eq!(res1.unwrap().end(), SessionEnd::Stopped);
+
+    std::fs::remove_dir_all(&added_path).unwrap();
+}
+
+#[tokio::test]
+async fn test_requested_shutdown() {
+    let load_path = PathBuf::from("test_data/test_shutdown");
+    assert!(load_path.exists());
+
+    let load_directoried = vec![load_path.clone()];
+
+    let default_service_name = String::from("default.service");
+    let app_service_name = String::from("app.service");
+    let base_service_name = String::from("base.service");
+    let stubborn_service_name = String::from("stubborn.service");
+
+    let mut nodes = HashMap::new();
+    NodeServiceDescriptor::load_tree(
+        &mut nodes,
+        &default_service_name,
+        load_directoried.as_slice(),
+    )
+    .await
+    .unwrap();
+
+    let manager = Arc::new(SessionManager::new(nodes, load_directoried, None));
+
+    // shutdown requests are what SIGTERM and SIGINT issue
+    let (res1, _) = join!(
+        timeout(Duration::from_secs(5), async {
+            let outcome = manager.run(&default_service_name).await;
+
+            // the main node is stopped first: the session ends before the others have exited
+            assert!(manager.is_running(&stubborn_service_name).await.unwrap());
+
+            manager.wait_for_shutdown().await;
+            for name in [
+                &default_service_name,
+                &app_service_name,
+                &base_service_name,
+                &stubborn_service_name,
+            ] {
+                assert!(!manager.is_running(name).await.unwrap());
+            }
+
+            outcome
+        }),
+        async {
+            sleep(Duration::from_millis(500)).await;
+
+            assert!(manager.request_shutdown(Duration::from_secs(1)));
+
+            // a second request does not start another shutdown
+            sleep(Duration::from_millis(100)).await;
+            assert!(!manager.request_shutdown(Duration::from_secs(1)));
+        }
+    );
+
+    assert_eq!(res1.unwrap().unwrap().end(), SessionEnd::Stopped);
+}
+
+#[tokio::test]
+async fn test_session_end_shutdown() {
+    let load_path = PathBuf::from("test_data/test_session_end");
+    assert!(load_path.exists());
+
+    let load_directoried = vec![load_path.clone()];
+
+    let default_service_name = String::from("default.service");
+    let worker_service_name = String::from("worker.service");
+
+    let mut nodes = HashMap::new();
+    NodeServiceDescriptor::load_tree(
+        &mut nodes,
+        &default_service_name,
+        load_directoried.as_slice(),
+    )
+    .await
+    .unwrap();
+
+    let manager = Arc::new(SessionManager::new(nodes, load_directoried, None));
+
+    // the main node exits by itself: the nodes it pulled in are still running
+    let outcome = timeout(Duration::from_secs(5), manager.run(&default_service_name))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(outcome.end(), SessionEnd::Completed);
+    assert!(manager.is_running(&worker_service_name).await.unwrap());
+
+    // as sessionrunner does before exiting, whatever ended the session
+    timeout(
+        Duration::from_secs(5),
+        manager.shutdown(Duration::from_secs(1)),
+    )
+    .await
+    .unwrap();
+    assert!(!manager.is_running(&worker_service_name).await.unwrap());
+}
+
+#[tokio::test]
+async fn test_daemon_reload() {
+    // descriptors are edited while the session is running
+    let load_path = std::env::temp_dir().join("sessionrunner_test_daemon_reload");
+    let _ = std::fs::remove_dir_all(&load_path);
+    std::fs::create_dir(&load_path).unwrap();
+
+    let sleeping = |seconds: u32, requires: &str| {
+        format!(
+            r#"{{ "kind": "service", "cmd": "sleep", "args": [ "{seconds}" ], "max_restarts": 0, "requires": [ {requires} ] }}"#
+        )
+    };
+    let write =
+        |name: &str, content: String| std::fs::write(load_path.join(name), content).unwrap();
+
+    write("default.service", sleeping(30, ""));
+    write("running.service", sleeping(30, ""));
+    write("idle.service", sleeping(30, ""));
+    write("user.service", sleeping(30, r#""idle.service""#));
+    write("gone.service", sleeping(30, ""));
+
+    let load_directoried = vec![load_path.clone()];
+
+    let default_service_name = String::from("default.service");
+    let running_service_name = String::from("running.service");
+    let idle_service_name = String::from("idle.service");
+    let user_service_name = String::from("user.service");
+    let gone_service_name = String::from("gone.service");
+    let new_service_name = String::from("new.service");
+
+    let mut nodes = HashMap::new();
+    NodeServiceDescriptor::load_tree(
+        &mut nodes,
+        &default_service_name,
+        load_directoried.as_slice(),
+    )
+    .await
+    .unwrap();
+
+    let errors = NodeServiceDescriptor::load_all(&mut nodes, load_directoried.as_slice()).await;
+    assert!(errors.is_empty());
+
+    let manager = Arc::new(SessionManager::new(nodes, load_directoried, None));
+
+    let (res1, res2) = join!(manager.run(&default_service_name), async {
+        manager.start(&running_service_name).await.unwrap();
+        manager.start(&gone_service_name).await.unwrap();
+        sleep(Duration::from_millis(300)).await;
+
+        write("running.service", sleeping(31, ""));
+        write("idle.service", sleeping(31, ""));
+        write("new.service", sleeping(30, ""));
+        std::fs::remove_file(load_path.join(&gone_service_name)).unwrap();
+
+        let outcome = manager.daemon_reload().await.unwrap();
+        assert_eq!(outcome.added(), [new_service_name.as_str()]);
+        assert_eq!(outcome.removed(), [gone_service_name.as_str()]);
+        assert_eq!(outcome.changed(), [idle_service_name.as_str()]);
+        assert_eq!(outcome.needs_restart(), [running_service_name.as_str()]);
+
+        // the running node has not been restarted
+        assert!(manager.is_running(&running_service_name).await.unwrap());
+        assert!(manager.needs_restart(&running_service_name).await.unwrap());
+        assert_eq!(
+            manager.history(&running_service_name).await.unwrap().len(),
+            0
+        );
+
+        assert!(matches!(
+            manager.kind(&gone_service_name).await,
+            Err(SessionManagerError::NotFound(_))
+        ));
+
+        // reloaded nodes depend on the reloaded versions of their dependencies
+        manager.start(&user_service_name).await.unwrap();
+        sleep(Duration::from_millis(300)).await;
+        assert!(manager.is_running(&idle_service_name).await.unwrap());
+        manager
+            .stop(&idle_service_name, StopMode::Cascade)
+            .await
+            .unwrap();
+
+        // a broken descriptor no node references is skipped
+        write("broken.service", String::from("{ \"kind\": "));
+        let outcome = manager.daemon_reload().await.unwrap();
+        assert_eq!(outcome.skipped().len(), 1);
+        assert!(outcome.skipped()[0].starts_with("broken.service: "));
+        assert!(manager.needs_restart(&running_service_name).await.unwrap());
+        std::fs::remove_file(load_path.join("broken.service")).unwrap();
+
+        // while a broken loaded one leaves the running configuration untouched
+        write("idle.service", String::from("{ \"kind\": "));
+        assert!(matches!(
+            manager.daemon_reload().await,
+            Err(SessionManagerError::ReloadFailed(_))
+        ));
+        assert!(manager.needs_restart(&running_service_name).await.unwrap());
+        write("idle.service", sleeping(31, ""));
+
+        // nodes loaded again keep the runs of the ones they replace
+        write("idle.service", sleeping(32, ""));
+        let outcome = manager.daemon_reload().await.unwrap();
+        assert_eq!(outcome.changed(), [idle_service_name.as_str()]);
+        assert_eq!(manager.history(&idle_service_name).await.unwrap().len(), 1);
+        assert!(matches!(
+            manager.status(&idle_service_name).await.unwrap(),
+            SessionNodeStatus::Stopped { .. }
+        ));
+        write("idle.service", sleeping(31, ""));
+        manager.daemon_reload().await.unwrap();
+
+        // once restarted the node runs with its new definition
+        manager.restart(&running_service_name).await.unwrap();
+        sleep(Duration::from_millis(300)).await;
+        assert!(manager.is_running(&running_service_name).await.unwrap());
+        assert!(!manager.needs_restart(&running_service_name).await.unwrap());
+        assert_eq!(
+            manager
+                .source(&running_service_name)
+                .await
+                .unwrap()
+                .unwrap()
+                .definition()["args"][0],
+            "31"
+        );
+        assert_eq!(
+            manager.history(&running_service_name).await.unwrap().len(),
+            1
+        );
+
+        let outcome = manager.daemon_reload().await.unwrap();
+        assert!(outcome.changed().is_empty());
+        assert!(outcome.needs_restart().is_empty());
+
+        manager
+            .stop(&running_service_name, StopMode::Plain)
+            .await
+            .unwrap();
+
+        manager.stop(&default_service_name, StopMode::Plain).await
+    });
+
+    res1.unwrap();
+    res2.unwrap();
+
+    std::fs::remove_dir_all(&load_path).unwrap();
+}
+
+#[tokio::test]
+async fn test_transient() {
+    let load_path = PathBuf::from("test_data/test_transient");
+    assert!(load_path.exists());
+
+    let load_directoried = vec![load_path.clone()];
+
+    let default_service_name = String::from("default.service");
+    let database_service_name = String::from("database.service");
+    let worker_service_name = String::from("worker.service");
+    let kept_service_name = String::from("kept.service");
+
+    let mut nodes = HashMap::new();
+    NodeServiceDescriptor::load_tree(
+        &mut nodes,
+        &default_service_name,
+        load_directoried.as_slice(),
+    )
+    .await
+    .unwrap();
+
+    let errors = NodeServiceDescriptor::load_all(&mut nodes, load_directoried.as_slice()).await;
+    assert!(errors.is_empty());
+
+    let manager = Arc::new(SessionManager::new(nodes, load_directoried, None));
+
+    let (res1, res2) = join!(manager.run(&default_service_name), async {
+        let worker = r#"{ "kind": "service", "cmd": "sleep", "args": [ "30" ], "requires": [ "database.service" ], "after": [ "database.service" ] }"#;
+        SessionManager::start_transient(manager.clone(), &worker_service_name, worker, false)
+            .await
+            .unwrap();
+        sleep(Duration::from_millis(300)).await;
+
+        // dependencies are pulled in from the search path
+        assert!(manager.is_running(&worker_service_name).await.unwrap());
+        assert!(manager.is_transient(&worker_service_name).await.unwrap());
+        assert!(manager.is_running(&database_service_name).await.unwrap());
+        assert!(manager
+            .source(&worker_service_name)
+            .await
+            .unwrap()
+            .is_none());
+
+        // names already in use and invalid descriptors are refused
+        assert!(matches!(
+            SessionManager::start_transient(manager.clone(), &database_service_name, worker, false)
+                .await,
+            Err(SessionManagerError::InvalidArgument(_))
+        ));
+        assert!(matches!(
+            SessionManager::start_transient(
+                manager.clone(),
+                &kept_service_name,
+                r#"{ "kind": "target", "cmd": "sleep" }"#,
+                true
+            )
+            .await,
+            Err(SessionManagerError::LoadingError(_))
+        ));
+        assert!(matches!(
+            manager.kind(&kept_service_name).await,
+            Err(SessionManagerError::NotFound(_))
+        ));
+
+        // transient nodes survive a reload of the configuration
+        let outcome = manager.daemon_reload().await.unwrap();
+        assert!(outcome.removed().is_empty());
+        assert!(manager.is_running(&worker_service_name).await.unwrap());
+
+        // once stopped the node is gone
+        manager
+            .stop(&worker_service_name, StopMode::Plain)
+            .await
+            .unwrap();
+        sleep(Duration::from_millis(300)).await;
+        assert!(matches!(
+            manager.kind(&worker_service_name).await,
+            Err(SessionManagerError::NotFound(_))
+        ));
+        assert!(manager.is_running(&database_service_name).await.unwrap());
+
+        // unless asked to be kept
+        let kept = r#"{ "kind": "oneshot", "cmd": "true" }"#;
+        SessionManager::start_transient(manager.clone(), &kept_service_name, kept, true)
+            .await
+            .unwrap();
+        sleep(Duration::from_millis(300)).await;
+        assert!(!manager.is_running(&kept_service_name).await.unwrap());
+        assert_eq!(manager.history(&kept_service_name).await.unwrap().len(), 1);
+        manager.start(&kept_service_name).await.unwrap();
+        sleep(Duration::from_millis(300)).await;
+        assert_eq!(manager.history(&kept_service_name).await.unwrap().len(), 2);
+
+        manager
+            .stop(&database_service_name, StopMode::Plain)
+            .await
+            .unwrap();
+        manager.stop(&default_service_name, StopMode::Plain).await
+    });
+
+    res1.unwrap();
+    res2.unwrap();
+}
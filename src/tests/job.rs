@@ -0,0 +1,120 @@
+/*
+    login-ng A greeter written in rust that also supports autologin with systemd-homed
+    Copyright (C) 2024-2025  Denis Benato
+
+    This program is free software; you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation; either version 2 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License along
+    with this program; if not, write to the Free Software Foundation, Inc.,
+    51 Franklin Street, Fifth Floor, Boston, MA 02110-1301 USA.
+*/
+
+use crate::job::{JobKind, JobQueue, JobState};
+
+fn jobs(transaction: &[(&str, JobKind)]) -> Vec<(String, JobKind)> {
+    transaction
+        .iter()
+        .map(|(node, kind)| (String::from(*node), *kind))
+        .collect()
+}
+
+#[test]
+fn test_job_merge_order() {
+    let mut queue = JobQueue::default();
+
+    let first = queue
+        .add_transaction(&jobs(&[("y.service", JobKind::Start)]), false)
+        .unwrap();
+
+    // the waiting start of y runs before the stop of x: it cannot be shared
+    let second = queue
+        .add_transaction(
+            &jobs(&[("x.service", JobKind::Stop), ("y.service", JobKind::Start)]),
+            false,
+        )
+        .unwrap();
+    assert_ne!(first[0], second[1]);
+
+    // the start of y queued after the stop of x can
+    let third = queue
+        .add_transaction(
+            &jobs(&[("x.service", JobKind::Stop), ("y.service", JobKind::Start)]),
+            false,
+        )
+        .unwrap();
+    assert_eq!(second, third);
+
+    let order = queue.list().iter().map(|job| job.id()).collect::<Vec<_>>();
+    assert_eq!(order, [first[0], second[0], second[1]]);
+}
+
+#[test]
+fn test_job_failure_aborts_transaction() {
+    let mut queue = JobQueue::default();
+
+    let ids = queue
+        .add_transaction(
+            &jobs(&[("x.service", JobKind::Stop), ("y.service", JobKind::Start)]),
+            false,
+        )
+        .unwrap();
+    let other = queue
+        .add_transaction(&jobs(&[("z.service", JobKind::Start)]), false)
+        .unwrap();
+
+    let job = queue.next().unwrap();
+    assert_eq!(job.id(), ids[0]);
+    queue.finish(job.id(), JobState::Failed(String::from("failed")));
+
+    assert_eq!(*queue.get(ids[1]).unwrap().state(), JobState::Cancelled);
+    assert_eq!(*queue.get(other[0]).unwrap().state(), JobState::Waiting);
+    assert_eq!(queue.next().unwrap().id(), other[0]);
+}
+
+#[test]
+fn test_job_awaited_kept() {
+    let mut queue = JobQueue::default();
+
+    // more jobs than the finished ones that are remembered
+    let transaction = (0..100)
+        .map(|n| (format!("node{n}.service"), JobKind::Start))
+        .collect::<Vec<_>>();
+    let ids = queue.add_transaction(&transaction, true).unwrap();
+
+    while let Some(job) = queue.next() {
+        queue.finish(job.id(), JobState::Done);
+    }
+
+    for id in ids.iter() {
+        assert_eq!(*queue.get(*id).unwrap().state(), JobState::Done);
+    }
+
+    queue.release(ids.as_slice());
+    assert!(queue.get(ids[0]).is_none());
+    assert!(queue.get(ids[99]).is_some());
+}
+
+#[test]
+fn test_job_cancel_aborts_transaction() {
+    let mut queue = JobQueue::default();
+
+    // the stop of a conflicting node followed by the start it makes room for
+    let ids = queue
+        .add_transaction(
+            &jobs(&[("x.service", JobKind::Stop), ("y.service", JobKind::Start)]),
+            false,
+        )
+        .unwrap();
+
+    assert!(queue.cancel(ids[0]));
+    assert_eq!(*queue.get(ids[1]).unwrap().state(), JobState::Cancelled);
+    assert!(queue.next().is_none());
+}
@@ -18,3 +18,4 @@
 */
 
 pub mod desc;
+pub mod job;